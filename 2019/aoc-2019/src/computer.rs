@@ -1,14 +1,134 @@
-use std::{collections::HashMap, fs};
-
-use anyhow::{Context, Result, anyhow, bail};
+use std::{collections::VecDeque, fs, sync::{Arc, Mutex}};
+
+use anyhow::{Context, Result, anyhow};
+
+pub(crate) use error::ComputerError;
+use bigint::BigInt;
+use history::History;
+pub(crate) use io::IntcodeIo;
+use io::IterIo;
+use livelock::LivelockDetector;
+pub(crate) use memory::Memory;
+use opcodes::Flow;
+
+pub(crate) mod ascii;
+pub(crate) mod asm;
+pub(crate) mod bench;
+pub(crate) mod bigint;
+pub(crate) mod cfg;
+pub(crate) mod coverage;
+pub(crate) mod decompile;
+pub(crate) mod diff;
+pub(crate) mod debugger;
+pub(crate) mod disasm;
+pub(crate) mod fuzz;
+pub(crate) mod error;
+pub(crate) mod history;
+pub(crate) mod io;
+pub(crate) mod livelock;
+pub(crate) mod memory;
+pub(crate) mod opcodes;
+pub(crate) mod profile;
+pub(crate) mod repl;
+pub(crate) mod snapshot;
+pub(crate) mod symbolic;
+pub(crate) mod threaded;
+pub(crate) mod trace;
+pub(crate) mod transpile;
 
 #[derive(Clone, Debug)]
 pub(crate) struct Computer {
     pub(crate) memory: Memory,
     pub(crate) ip: u64,
     pub(crate) rel_base: i64,
+    // Inputs consumed before the ones passed to `run`, e.g. inputs given
+    // back by rewinding.
+    pub(crate) pending_input: VecDeque<i64>,
+    pub(crate) arithmetic: Arithmetic,
+    history: Option<History>,
+    livelock: Option<LivelockDetector>,
+    observers: Observers,
+}
+
+// How `ADD` and `MUL` deal with results outside the i64 range.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub(crate) enum Arithmetic {
+    // Wrap around like two's complement hardware would.
+    Wrapping,
+    // Stop with `ComputerError::Overflow`.
+    #[default]
+    Checked,
+    // Keep the exact value in the memory's big-integer cells. Such values
+    // can only be used by arithmetic and comparisons, anything else that
+    // reads one stops with `ComputerError::ValueOutOfRange`.
+    BigInt,
+}
+
+#[allow(clippy::needless_return)]
+impl Arithmetic {
+    pub(crate) fn name(&self) -> &'static str {
+        return match self {
+            Arithmetic::Wrapping => "wrapping",
+            Arithmetic::Checked => "checked",
+            Arithmetic::BigInt => "bigint",
+        };
+    }
+
+    pub(crate) fn from_name(name: &str) -> Option<Arithmetic> {
+        return [Arithmetic::Wrapping, Arithmetic::Checked, Arithmetic::BigInt].into_iter()
+            .find(|a| a.name() == name);
+    }
+}
+
+// Hooks called around every executed instruction. Observers are shared
+// between clones of a `Computer`, so one observer sees every VM spawned from
+// the same program.
+#[allow(clippy::needless_return)]
+pub(crate) trait Observer: Send {
+    fn before_step(&mut self, _computer: &mut Computer)
+            -> std::result::Result<(), ComputerError> {
+        return Ok(());
+    }
+
+    fn after_step(&mut self, _computer: &Computer, _event: &Event) {}
+}
+
+// What a single executed instruction did.
+#[derive(Debug)]
+pub(crate) struct Event {
+    pub(crate) ip: u64,
+    pub(crate) instruction: Instruction,
+    pub(crate) write: Option<MemoryWrite>,
 }
 
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct MemoryWrite {
+    pub(crate) addr: u64,
+    pub(crate) old: i64,
+    pub(crate) new: i64,
+}
+
+pub(crate) type SharedObserver = Arc<Mutex<dyn Observer>>;
+
+#[derive(Clone, Default)]
+struct Observers(Vec<SharedObserver>);
+
+#[allow(clippy::needless_return)]
+impl std::fmt::Debug for Observers {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return write!(f, "Observers({})", self.0.len());
+    }
+}
+
+// Observers attached to every `Computer` created from now on, used by the
+// runner tools to instrument whole days.
+static GLOBAL_OBSERVERS: Mutex<Vec<SharedObserver>> = Mutex::new(Vec::new());
+
+pub(crate) fn attach_global_observer(observer: SharedObserver) {
+    GLOBAL_OBSERVERS.lock().unwrap().push(observer);
+}
+
+#[allow(clippy::needless_return)]
 impl Computer {
     pub(crate) fn from_file(input_file: &str) -> Result<Self> {
         let contents = fs::read_to_string(input_file)
@@ -16,409 +136,588 @@ impl Computer {
         let contents = contents.split("\n").next()
             .context("Error while parsing the input file")?;
 
+        let program = contents.split(",")
+            .map(|ch| ch.parse::<i64>())
+            .collect::<Result<Vec<_>, _>>()
+            .context("Failed to parse input file")?;
+
+        return Ok(Computer::from_program(&program));
+    }
+
+    pub(crate) fn from_program(program: &[i64]) -> Self {
         let mut memory = Memory::new();
-        for (idx, ch) in contents.split(",").enumerate() {
-            let val = ch.parse::<i64>()
-                .context("Failed to parse input file")?;
-            memory.write(idx as u64, val);
+        for (idx, val) in program.iter().enumerate() {
+            memory.write(idx as u64, *val);
         }
 
-        return Ok(Computer {
+        return Computer {
             memory,
             ip: 0,
             rel_base: 0,
-        });
+            pending_input: VecDeque::new(),
+            arithmetic: Arithmetic::default(),
+            history: None,
+            livelock: None,
+            observers: Observers(GLOBAL_OBSERVERS.lock().unwrap().clone()),
+        };
     }
 
-    pub(crate) fn run(&mut self, mut inputs: impl Iterator<Item=i64>)
-            -> Result<RunOutput> {
-        let mut outputs = Vec::<i64>::new();
+    pub(crate) fn run(&mut self, inputs: impl Iterator<Item=i64>)
+            -> std::result::Result<RunOutput, ComputerError> {
+        return self.run_with_budget(inputs, u64::MAX);
+    }
+
+    // Same as `run`, but stops with `Status::BudgetExhausted` after executing
+    // `budget` instructions. Running again picks up where it stopped.
+    pub(crate) fn run_with_budget(&mut self, inputs: impl Iterator<Item=i64>, budget: u64)
+            -> std::result::Result<RunOutput, ComputerError> {
+        let mut io = IterIo::new(inputs);
+        let status = self.run_io_with_budget(&mut io, budget)?;
+
+        return Ok(RunOutput { outputs: io.outputs, status });
+    }
+
+    // Runs with `io` handling every input and output as soon as the program
+    // gets to it.
+    pub(crate) fn run_io(&mut self, io: &mut impl IntcodeIo)
+            -> std::result::Result<Status, ComputerError> {
+        return self.run_io_with_budget(io, u64::MAX);
+    }
+
+    pub(crate) fn run_io_with_budget(&mut self, io: &mut impl IntcodeIo, budget: u64)
+            -> std::result::Result<Status, ComputerError> {
+        let status = self.execute(io, budget, false)?;
+        return Ok(status.expect("only stops after an output when asked to"));
+    }
+
+    // Attaches an observer to this VM and the clones made from it later on.
+    pub(crate) fn attach_observer(&mut self, observer: SharedObserver) {
+        self.observers.0.push(observer);
+    }
+
+    // Runs until the program outputs a value, needs an input that isn't in
+    // `pending_input` or halts.
+    pub(crate) fn step(&mut self) -> std::result::Result<Step, ComputerError> {
+        let mut io = IterIo::new(std::iter::empty());
+        let step = match self.execute(&mut io, u64::MAX, true)? {
+            None => Step::Output(io.outputs[0]),
+            Some(Status::Halted) => Step::Halted,
+            Some(Status::PausedForInput) => Step::NeedInput,
+            Some(Status::Livelocked) => return Err(self.livelocked()),
+            Some(Status::BudgetExhausted) => unreachable!("`step` has no instruction budget"),
+        };
+
+        return Ok(step);
+    }
+
+    // The next output, or `None` if the program halts first. Needing more
+    // input than what's pending is an error.
+    pub(crate) fn run_until_output(&mut self)
+            -> std::result::Result<Option<i64>, ComputerError> {
+        return match self.step()? {
+            Step::Output(val) => Ok(Some(val)),
+            Step::Halted => Ok(None),
+            Step::NeedInput => Err(self.input_exhausted()),
+        };
+    }
+
+    pub(crate) fn push_input(&mut self, val: i64) {
+        self.pending_input.push_back(val);
+    }
+
+    // Returns `None` when it stopped right after an output because of
+    // `until_output`.
+    fn execute(&mut self, io: &mut impl IntcodeIo, budget: u64, until_output: bool)
+            -> std::result::Result<Option<Status>, ComputerError> {
+        self.reset_livelock_detection();
+
+        let mut executed = 0;
         loop {
-            // println!("IP = {}", self.ip);
+            if executed == budget {
+                return Ok(Some(Status::BudgetExhausted));
+            }
+            executed += 1;
+
+            let observed = !self.observers.0.is_empty();
+            if observed {
+                self.notify_before_step()?;
+            }
+
+            let tracked = observed || self.history.is_some() || self.livelock.is_some();
+            let ip = self.ip;
+            let rel_base = self.rel_base;
             let instruction = self.parse_instruction()?;
-            // println!("instruction = {instruction:#?}");
-            match instruction {
-                Instruction::Add { a, b, pos } => {
-                    self.memory.write(pos as u64, a + b);
-                    self.ip += instruction.size();
+            if self.arithmetic == Arithmetic::BigInt && self.memory.has_big_cells() {
+                self.check_big_operands(&instruction)?;
+            }
+            let old = match tracked {
+                true => instruction.write_addr().map(|addr| (addr, self.memory.read(addr))),
+                false => None,
+            };
+            // Values that don't fit in an i64 are kept aside for the undo log,
+            // `MemoryWrite` only has room for plain ones.
+            let old_big = match old {
+                Some((addr, _)) if self.history.is_some() && self.memory.is_big(addr) =>
+                    Some(self.memory.read_big(addr)),
+                _ => None,
+            };
+
+            // Registered opcodes might do I/O too, which `step` and livelock
+            // detection have to see.
+            let pending = self.pending_input.len();
+            let mut counting = CountingIo { io, reads: 0, writes: 0 };
+            let flow = opcodes::exec(self, &mut counting, &instruction)?;
+            let output = counting.writes > 0;
+            let did_io = output || counting.reads > 0 || self.pending_input.len() != pending;
+            match flow {
+                Flow::Next => self.ip += instruction.size(),
+                Flow::Jump(addr) => self.ip = addr,
+                Flow::Halt => {},
+                Flow::NeedInput => return Ok(Some(Status::PausedForInput)),
+            }
+
+            let halted = flow == Flow::Halt;
+
+            if tracked {
+                let write = old.map(|(addr, old)| MemoryWrite {
+                    addr,
+                    old,
+                    new: self.memory.read(addr),
+                });
+                let event = Event { ip, instruction, write };
+
+                if let Some(history) = &mut self.history {
+                    history.record(&event, rel_base, old_big);
+                }
+                if observed {
+                    self.notify_after_step(&event);
                 }
-                Instruction::Mul { a, b, pos } => {
-                    self.memory.write(pos as u64, a * b);
-                    self.ip += instruction.size();
-                },
-                Instruction::Input(param) => {
-                    let next = inputs.next();
-                    if next.is_none() {
-                        return Ok(RunOutput {
-                            outputs,
-                            status: Status::PausedForInput,
-                        });
-                    }
-
-                    self.memory.write(param as u64, next.unwrap());
-                    self.ip += instruction.size();
-                },
-                Instruction::Output(val) => {
-                    outputs.push(val);
-                    self.ip += instruction.size();
+
+                let livelocked = match self.livelock.take() {
+                    Some(mut detector) => {
+                        let stuck = !halted && detector.record(self, &event, did_io);
+                        self.livelock = Some(detector);
+                        stuck
+                    },
+                    None => false,
+                };
+                if livelocked {
+                    return Ok(Some(Status::Livelocked));
                 }
-                Instruction::JumpIfTrue { val, pos } => {
-                    if val != 0 {
-                        self.ip = pos as u64;
-                    } else {
-                        self.ip += instruction.size();
-                    }
-                },
-                Instruction::JumpIfFalse { val, pos } => {
-                    if val == 0 {
-                        self.ip = pos as u64;
-                    } else {
-                        self.ip += instruction.size();
-                    }
-                },
-                Instruction::LessThan { a, b, pos } => {
-                    if a < b {
-                        self.memory.write(pos as u64, 1);
-                    } else {
-                        self.memory.write(pos as u64, 0);
-                    }
-                    self.ip += instruction.size();
-                },
-                Instruction::Equals { a, b, pos } => {
-                    if a == b {
-                        self.memory.write(pos as u64, 1);
-                    } else {
-                        self.memory.write(pos as u64, 0);
-                    }
-                    self.ip += instruction.size();
-                },
-                Instruction::AdjRelBase(val) => {
-                    self.rel_base += val;
-                    self.ip += instruction.size();
-                },
-                Instruction::Halt => {
-                    return Ok(RunOutput { outputs, status: Status::Halted });
-                },
+            }
+
+            if halted {
+                return Ok(Some(Status::Halted));
+            }
+            if until_output && output {
+                return Ok(None);
             }
         }
     }
 
-    fn parse_instruction(&mut self) -> Result<Instruction> {
-        let instruction = self.memory.read(self.ip);
-        // println!("instruction = {instruction}");
-        let op = instruction % 100;
-        let param_modes = instruction / 100;
-        let ret = match op {
-            1 => {
-                // add
-                let (p1, p2, p3) = self.parse_params3(
-                    param_modes,
-                    self.memory.read(self.ip + 1),
-                    self.memory.read(self.ip + 2),
-                    self.memory.read(self.ip + 3),
-                )?;
-
-                let a = self.fetch_param(p1);
-                let b = self.fetch_param(p2);
-                let pos = self.fetch_pos_param(p3)
-                    .context("Failed to fetch output param of add instruction")?;
-
-                Instruction::Add { a, b, pos }
-            },
-            2 => {
-                // mul
-                let (p1, p2, p3) = self.parse_params3(
-                    param_modes,
-                    self.memory.read(self.ip + 1),
-                    self.memory.read(self.ip + 2),
-                    self.memory.read(self.ip + 3),
-                )?;
-
-                let a = self.fetch_param(p1);
-                let b = self.fetch_param(p2);
-                let pos = self.fetch_pos_param(p3)
-                    .context("Failed to fetch output param of mul instruction")?;
-
-
-                Instruction::Mul { a, b, pos }
-            },
-            3 => {
-                // input
-                let param = self.parse_params1(
-                    param_modes,
-                    self.memory.read(self.ip + 1)
-                )?;
+    // Runs until the program halts and returns everything it printed. Asking
+    // for more input than given is an error.
+    pub(crate) fn run_to_halt(&mut self, inputs: impl Iterator<Item=i64>)
+            -> std::result::Result<Vec<i64>, ComputerError> {
+        let output = self.run(inputs)?;
+        return match output.status {
+            Status::Halted => Ok(output.outputs),
+            Status::PausedForInput => Err(self.input_exhausted()),
+            Status::Livelocked => Err(self.livelocked()),
+            Status::BudgetExhausted => unreachable!("`run` has no instruction budget"),
+        };
+    }
 
-                let pos = self.fetch_pos_param(param)
-                    .context("Failed to fetch pos param of input instruction")?;
+    fn arithmetic_op(&mut self, opcode: Opcode, a: i64, b: i64, pos: u64)
+            -> std::result::Result<(), ComputerError> {
+        let checked = match opcode {
+            Opcode::Add => a.checked_add(b),
+            _ => a.checked_mul(b),
+        };
 
-                Instruction::Input(pos)
-            },
-            4 => {
-                // output
-                let param = self.parse_params1(
-                    param_modes,
-                    self.memory.read(self.ip + 1)
-                )?;
-                let val = self.fetch_param(param);
-
-                Instruction::Output(val)
-            },
-            5 => {
-                // jump-if-true
-                let (p1, p2) = self.parse_params2(
-                    param_modes,
-                    self.memory.read(self.ip + 1),
-                    self.memory.read(self.ip + 2),
-                )?;
-                let val = self.fetch_param(p1);
-                let pos = self.fetch_param(p2) as u64;
-
-                Instruction::JumpIfTrue { val, pos }
-            },
-            6 => {
-                // jump-if-false
-                let (p1, p2) = self.parse_params2(
-                    param_modes,
-                    self.memory.read(self.ip + 1),
-                    self.memory.read(self.ip + 2),
-                )?;
-                let val = self.fetch_param(p1);
-                let pos = self.fetch_param(p2) as u64;
-
-                Instruction::JumpIfFalse { val, pos }
+        match self.arithmetic {
+            Arithmetic::Wrapping => {
+                let res = match opcode {
+                    Opcode::Add => a.wrapping_add(b),
+                    _ => a.wrapping_mul(b),
+                };
+                self.memory.write(pos, res);
             },
-            7 => {
-                // less than
-                let (p1, p2, p3) = self.parse_params3(
-                    param_modes,
-                    self.memory.read(self.ip + 1),
-                    self.memory.read(self.ip + 2),
-                    self.memory.read(self.ip + 3),
-                )?;
-
-                let a = self.fetch_param(p1);
-                let b = self.fetch_param(p2);
-                let pos = self.fetch_pos_param(p3)
-                    .context("Failed to fetch pos param for less-than instruction")?;
-
-                Instruction::LessThan { a, b, pos }
+            Arithmetic::Checked => {
+                let res = checked_arithmetic(self.ip, self.rel_base, opcode, a, b)?;
+                self.memory.write(pos, res);
             },
-            8 => {
-                // equals
-                let (p1, p2, p3) = self.parse_params3(
-                    param_modes,
-                    self.memory.read(self.ip + 1),
-                    self.memory.read(self.ip + 2),
-                    self.memory.read(self.ip + 3),
-                )?;
-
-                let a = self.fetch_param(p1);
-                let b = self.fetch_param(p2);
-                let pos = self.fetch_pos_param(p3)
-                    .context("Failed to fetch pos param for equals instruction")?;
-
-                Instruction::Equals { a, b, pos }
+            Arithmetic::BigInt => {
+                if let Some(res) = checked && !self.memory.has_big_cells() {
+                    self.memory.write(pos, res);
+                    return Ok(());
+                }
+
+                let (a, b) = self.big_operands()?;
+                let res = match opcode {
+                    Opcode::Add => &a + &b,
+                    _ => &a * &b,
+                };
+                self.memory.write_big(pos, res);
             },
-            9 => {
-                // adjust relative base
-                let param = self.parse_params1(
-                    param_modes,
-                    self.memory.read(self.ip + 1),
-                )?;
+        }
 
-                let val = self.fetch_param(param);
+        return Ok(());
+    }
 
-                Instruction::AdjRelBase(val)
-            },
-            99 => {
-                // halt
-                Instruction::Halt
-            },
-            op => {
-                bail!("Invalid op code {op}");
-            }
-        };
+    fn compare(&mut self, a: i64, b: i64)
+            -> std::result::Result<std::cmp::Ordering, ComputerError> {
+        if self.arithmetic != Arithmetic::BigInt || !self.memory.has_big_cells() {
+            return Ok(a.cmp(&b));
+        }
 
-        return Ok(ret);
+        let (a, b) = self.big_operands()?;
+        return Ok(a.cmp(&b));
     }
 
-    fn fetch_pos_param(&self, param: Parameter) -> Result<u64> {
-        let res = match param.mode {
-            ParameterMode::Position => param.val as u64,
-            ParameterMode::Relative => (param.val + self.rel_base) as u64,
-            m => {
-                bail!("Invalid mode `{m:?}` for output param.");
-            },
-        };
-        return Ok(res);
+    // Exact values of the first two operands of the instruction at `ip`.
+    fn big_operands(&mut self) -> std::result::Result<(BigInt, BigInt), ComputerError> {
+        let decoded = self.memory.decode(self.ip)?;
+        let mut operands = [0, 1].into_iter().map(|idx| {
+            return match decoded.param_addr(self.ip, idx, self.rel_base)? {
+                Some(addr) => Ok(self.memory.read_big(addr)),
+                None => Ok(BigInt::from(decoded.params[idx].val)),
+            };
+        });
+
+        let a = operands.next().unwrap()?;
+        let b = operands.next().unwrap()?;
+        return Ok((a, b));
     }
 
-    fn parse_params1(&self, modes: i64, val1: i64) -> Result<Parameter> {
-        let p1_mode = (modes % 10) as u8;
-        let p1_mode: ParameterMode = p1_mode.try_into()
-            .or_else(|_| bail!("Failed to parse parameter mode"))?;
+    // Makes sure instructions other than arithmetic and comparisons never
+    // read a value that only fits in a big-integer cell.
+    fn check_big_operands(&mut self, instruction: &Instruction)
+            -> std::result::Result<(), ComputerError> {
+        if matches!(instruction.opcode,
+                Opcode::Add | Opcode::Mul | Opcode::LessThan | Opcode::Equals) {
+            return Ok(());
+        }
 
-        let p1 = Parameter {
-            mode: p1_mode,
-            val: val1,
-        };
-        // println!("p1={p1:#?}");
+        let decoded = self.memory.decode(self.ip)?;
+        for idx in 0..decoded.params().len() {
+            if decoded.opcode.is_write_param(idx) {
+                continue;
+            }
 
-        return Ok(p1);
+            if let Some(addr) = decoded.param_addr(self.ip, idx, self.rel_base)?
+                    && self.memory.is_big(addr) {
+                return Err(ComputerError::ValueOutOfRange {
+                    ip: self.ip,
+                    rel_base: self.rel_base,
+                    opcode: decoded.opcode,
+                    param: idx + 1,
+                });
+            }
+        }
+
+        return Ok(());
+    }
+
+    fn notify_before_step(&mut self) -> std::result::Result<(), ComputerError> {
+        // Observers get mutable access to the VM, so they're moved out
+        // while being called.
+        let observers = std::mem::take(&mut self.observers);
+        let res = observers.0.iter()
+            .try_for_each(|o| o.lock().unwrap().before_step(self));
+        self.observers = observers;
+
+        return res;
+    }
+
+    fn notify_after_step(&mut self, event: &Event) {
+        for o in &self.observers.0 {
+            o.lock().unwrap().after_step(self, event);
+        }
     }
 
-    fn parse_params2(&self, modes: i64, val1: i64, val2: i64) ->
-            Result<(Parameter, Parameter)> {
-        let p2_mode = ((modes / 10) % 10) as u8;
-        let p1_mode = (modes % 10) as u8;
+    fn parse_instruction(&mut self) -> std::result::Result<Instruction, ComputerError> {
+        let decoded = self.memory.decode(self.ip)?;
+        let spec = opcodes::spec(decoded.opcode);
+        let mut operands = [0; MAX_PARAMS];
+        for (idx, &param) in decoded.params[..spec.arity as usize].iter().enumerate() {
+            operands[idx] = match spec.write_params.contains(&idx) {
+                true => self.fetch_pos_param(decoded.opcode, param, idx + 1)? as i64,
+                false => self.fetch_param(decoded.opcode, param)?,
+            };
+        }
+
+        return Ok(Instruction { opcode: decoded.opcode, operands });
+    }
 
-        let p1_mode: ParameterMode = p1_mode.try_into()
-            .or_else(|_| bail!("Failed to parse parameter mode"))?;
-        let p2_mode: ParameterMode = p2_mode.try_into()
-            .or_else(|_| bail!("Failed to parse parameter mode"))?;
+    fn address(&self, opcode: Opcode, addr: i64) -> std::result::Result<u64, ComputerError> {
+        return checked_address(self.ip, self.rel_base, opcode, addr);
+    }
 
-        let p1 = Parameter {
-            mode: p1_mode,
-            val: val1,
+    fn fetch_pos_param(&self, opcode: Opcode, param: Parameter, idx: usize)
+            -> std::result::Result<u64, ComputerError> {
+        return match param.mode {
+            ParameterMode::Position => self.address(opcode, param.val),
+            ParameterMode::Relative => relative_address(self.ip, self.rel_base, opcode, param.val),
+            ParameterMode::Immediate => Err(ComputerError::WriteToImmediate {
+                ip: self.ip,
+                rel_base: self.rel_base,
+                opcode,
+                word: self.memory.read(self.ip),
+                param: idx,
+            }),
         };
-        let p2 = Parameter {
-            mode: p2_mode,
-            val: val2,
+    }
+
+    fn fetch_param(&self, opcode: Opcode, p: Parameter) -> std::result::Result<i64, ComputerError> {
+        return match p.mode {
+            ParameterMode::Position => Ok(self.memory.read(self.address(opcode, p.val)?)),
+            ParameterMode::Immediate => Ok(p.val),
+            ParameterMode::Relative =>
+                Ok(self.memory.read(relative_address(self.ip, self.rel_base, opcode, p.val)?)),
         };
+    }
 
-        // println!("p1={p1:#?}");
-        // println!("p2={p2:#?}");
+    fn input_exhausted(&self) -> ComputerError {
+        return ComputerError::InputExhausted { ip: self.ip, rel_base: self.rel_base };
+    }
 
-        return Ok((p1, p2));
+    fn livelocked(&self) -> ComputerError {
+        return ComputerError::Livelocked { ip: self.ip, rel_base: self.rel_base };
     }
+}
 
-    fn parse_params3(&self, modes: i64, val1: i64, val2: i64, val3: i64) ->
-            Result<(Parameter, Parameter, Parameter)> {
-        let p3_mode = ((modes / 100) % 10) as u8;
-        let p2_mode = ((modes / 10) % 10) as u8;
-        let p1_mode = (modes % 10) as u8;
+// Faults shared by the interpreter and transpiled programs.
+#[allow(clippy::needless_return)]
+pub(crate) fn checked_address(ip: u64, rel_base: i64, opcode: Opcode, addr: i64)
+        -> std::result::Result<u64, ComputerError> {
+    if addr < 0 {
+        return Err(ComputerError::NegativeAddress { ip, rel_base, opcode, addr });
+    }
 
-        let p1_mode: ParameterMode = p1_mode.try_into()
-            .or_else(|_| bail!("Failed to parse parameter mode"))?;
-        let p2_mode: ParameterMode = p2_mode.try_into()
-            .or_else(|_| bail!("Failed to parse parameter mode"))?;
-        let p3_mode: ParameterMode = p3_mode.try_into()
-            .or_else(|_| bail!("Failed to parse parameter mode"))?;
+    return Ok(addr as u64);
+}
 
-        let p1 = Parameter {
-            mode: p1_mode,
-            val: val1,
-        };
-        let p2 = Parameter {
-            mode: p2_mode,
-            val: val2,
-        };
-        let p3 = Parameter {
-            mode: p3_mode,
-            val: val3,
-        };
+// Address `offset` from `rel_base`, as used by relative-mode parameters.
+#[allow(clippy::needless_return)]
+pub(crate) fn relative_address(ip: u64, rel_base: i64, opcode: Opcode, offset: i64)
+        -> std::result::Result<u64, ComputerError> {
+    let Some(addr) = offset.checked_add(rel_base) else {
+        return Err(ComputerError::AddressOverflow { ip, rel_base, opcode, offset });
+    };
+
+    return checked_address(ip, rel_base, opcode, addr);
+}
+
+#[allow(clippy::needless_return)]
+pub(crate) fn checked_arithmetic(ip: u64, rel_base: i64, opcode: Opcode, a: i64, b: i64)
+        -> std::result::Result<i64, ComputerError> {
+    let res = match opcode {
+        Opcode::Add => a.checked_add(b),
+        _ => a.checked_mul(b),
+    };
+    return res.ok_or(ComputerError::Overflow { ip, rel_base, opcode, a, b });
+}
+
+#[allow(clippy::needless_return)]
+pub(crate) fn checked_rel_base(ip: u64, rel_base: i64, operand: i64)
+        -> std::result::Result<i64, ComputerError> {
+    return rel_base.checked_add(operand).ok_or(ComputerError::Overflow {
+        ip,
+        rel_base,
+        opcode: Opcode::AdjRelBase,
+        a: rel_base,
+        b: operand,
+    });
+}
+
+// Opcode and raw parameters of the instruction at `ip`, without resolving
+// any operand against memory.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Decoded {
+    pub(crate) opcode: Opcode,
+    pub(crate) params: [Parameter; MAX_PARAMS],
+}
 
-        // println!("p1={p1:#?}");
-        // println!("p2={p2:#?}");
-        // println!("p3={p3:#?}");
+#[allow(clippy::needless_return)]
+impl Decoded {
+    pub(crate) fn params(&self) -> &[Parameter] {
+        return &self.params[..self.opcode.arity() as usize];
+    }
 
-        return Ok((p1, p2, p3));
+    pub(crate) fn size(&self) -> u64 {
+        return self.opcode.size();
     }
 
-    fn fetch_param(&self, p: Parameter) -> i64 {
+    // Address the parameter at `idx` refers to, `None` for immediates.
+    // Faults the same way running the instruction at `ip` would.
+    pub(crate) fn param_addr(&self, ip: u64, idx: usize, rel_base: i64)
+            -> std::result::Result<Option<u64>, ComputerError> {
+        let p = self.params[idx];
         return match p.mode {
-            ParameterMode::Position => self.memory.read(p.val as u64),
-            ParameterMode::Immediate => p.val,
-            ParameterMode::Relative =>
-                self.memory.read((p.val + self.rel_base) as u64),
+            ParameterMode::Position => checked_address(ip, rel_base, self.opcode, p.val).map(Some),
+            ParameterMode::Immediate => Ok(None),
+            ParameterMode::Relative => relative_address(ip, rel_base, self.opcode, p.val).map(Some),
         };
     }
 }
 
-#[derive(Clone, Debug)]
-pub(crate) struct Memory {
-    inner: HashMap<u64, i64>
+#[allow(clippy::needless_return)]
+pub(crate) fn decode(memory: &Memory, ip: u64) -> std::result::Result<Decoded, ComputerError> {
+    let word = memory.read(ip);
+    let opcode: Opcode = (word % 100).try_into()
+        .map_err(|_| ComputerError::InvalidOpcode { ip, word })?;
+
+    let mut modes = word / 100;
+    let mut params = [Parameter::default(); MAX_PARAMS];
+    for (idx, param) in params.iter_mut()
+            .take(opcode.arity() as usize)
+            .enumerate() {
+        let mode = ((modes % 10) as u8).try_into()
+            .map_err(|_| ComputerError::InvalidMode { ip, word, param: idx + 1 })?;
+        modes /= 10;
+
+        *param = Parameter {
+            mode,
+            val: memory.read(ip + 1 + idx as u64),
+        };
+    }
+
+    return Ok(Decoded { opcode, params });
 }
 
-impl Memory {
-    pub fn new() -> Self {
-        Self {
-            inner: HashMap::new()
-        }
+// An instruction with its operands resolved: values for the parameters it
+// reads, addresses for the ones it writes to.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) struct Instruction {
+    pub(crate) opcode: Opcode,
+    operands: [i64; MAX_PARAMS],
+}
+
+#[allow(clippy::needless_return)]
+impl Instruction {
+    fn size(&self) -> u64 {
+        return self.opcode.size();
     }
 
-    pub fn read(&self, addr: u64) -> i64 {
-        return *self.inner.get(&addr).unwrap_or(&0);
+    pub(crate) fn operands(&self) -> &[i64] {
+        return &self.operands[..self.opcode.arity() as usize];
     }
 
-    pub fn write(&mut self, addr: u64, val: i64) {
-        self.inner.insert(addr, val);
+    pub(crate) fn write_addr(&self) -> Option<u64> {
+        return (0..self.opcode.arity() as usize)
+            .find(|&idx| self.opcode.is_write_param(idx))
+            .map(|idx| self.operands[idx] as u64);
     }
 }
 
-#[derive(Debug)]
-enum Instruction {
-    Add {
-        a: i64,
-        b: i64,
-        pos: u64,
-    },
-    Mul {
-        a: i64,
-        b: i64,
-        pos: u64,
-    },
-    Input(u64),
-    Output(i64),
-    JumpIfTrue {
-        val: i64,
-        pos: u64,
-    },
-    JumpIfFalse{
-        val: i64,
-        pos: u64,
-    },
-    LessThan {
-        a: i64,
-        b: i64,
-        pos: u64,
-    },
-    Equals {
-        a: i64,
-        b: i64,
-        pos: u64,
-    },
-    AdjRelBase(i64),
-    Halt
+// Counts what goes through to `io`, so `execute` can tell which instructions
+// did I/O, registered ones included.
+struct CountingIo<'a> {
+    io: &'a mut dyn IntcodeIo,
+    reads: u64,
+    writes: u64,
 }
 
-impl Instruction {
-    fn size(&self) -> u64 {
-        return match self {
-            Instruction::Add { a: _a, b: _b, pos: _res } => 4,
-            Instruction::Mul { a: _a, b: _b, pos: _res } => 4,
-            Instruction::Input(_) => 2,
-            Instruction::Output(_) => 2,
-            Instruction::JumpIfTrue { val: _val, pos: _pos } => 3,
-            Instruction::JumpIfFalse { val: _val, pos: _pos } => 3,
-            Instruction::LessThan { a: _a, b: _b, pos: _pos } => 4,
-            Instruction::Equals { a: _a, b: _b, pos: _pos } => 4,
-            Instruction::AdjRelBase(_) => 2,
-            Instruction::Halt => 1,
-        };
+#[allow(clippy::needless_return)]
+impl IntcodeIo for CountingIo<'_> {
+    fn read(&mut self) -> Option<i64> {
+        self.reads += 1;
+        return self.io.read();
+    }
+
+    fn write(&mut self, val: i64) {
+        self.writes += 1;
+        self.io.write(val);
     }
 }
 
-#[derive(Debug)]
-struct Parameter {
-    mode: ParameterMode,
-    val: i64,
+pub(crate) const MAX_PARAMS: usize = 3;
+
+// Arity, write parameters, mnemonic and behaviour of each opcode are in its
+// `opcodes::OpcodeSpec`. Codes other than the built-in ones only decode once
+// registered with `opcodes::register`, which is where `Custom` ones come from.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum Opcode {
+    Add,
+    Mul,
+    Input,
+    Output,
+    JumpIfTrue,
+    JumpIfFalse,
+    LessThan,
+    Equals,
+    AdjRelBase,
+    Halt,
+    // Holds the code, the spec is looked up in the registry.
+    Custom(u8),
+}
+
+#[allow(clippy::needless_return)]
+impl Opcode {
+    // The built-in opcodes.
+    pub(crate) const ALL: [Opcode; 10] = [
+        Opcode::Add,
+        Opcode::Mul,
+        Opcode::Input,
+        Opcode::Output,
+        Opcode::JumpIfTrue,
+        Opcode::JumpIfFalse,
+        Opcode::LessThan,
+        Opcode::Equals,
+        Opcode::AdjRelBase,
+        Opcode::Halt,
+    ];
+
+    // The last two digits of an instruction word.
+    pub(crate) fn code(&self) -> u8 {
+        return opcodes::spec(*self).code;
+    }
+
+    pub(crate) fn arity(&self) -> u64 {
+        return opcodes::spec(*self).arity;
+    }
+
+    pub(crate) fn size(&self) -> u64 {
+        return self.arity() + 1;
+    }
+
+    // Whether the parameter at `idx` is an address the instruction writes to.
+    pub(crate) fn is_write_param(&self, idx: usize) -> bool {
+        return opcodes::spec(*self).write_params.contains(&idx);
+    }
+
+    pub(crate) fn mnemonic(&self) -> &'static str {
+        return opcodes::spec(*self).mnemonic;
+    }
+
+    pub(crate) fn from_mnemonic(mnemonic: &str) -> Option<Opcode> {
+        return opcodes::all().into_iter()
+            .find(|op| op.mnemonic().eq_ignore_ascii_case(mnemonic));
+    }
+}
+
+#[allow(clippy::needless_return)]
+impl TryFrom<i64> for Opcode {
+    type Error = anyhow::Error;
+
+    fn try_from(value: i64) -> std::result::Result<Self, Self::Error> {
+        return u8::try_from(value).ok()
+            .and_then(opcodes::lookup)
+            .ok_or_else(|| anyhow!("Invalid op code {value}"));
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct Parameter {
+    pub(crate) mode: ParameterMode,
+    pub(crate) val: i64,
 }
 
 #[repr(u8)]
-#[derive(Debug, Eq, PartialEq)]
-enum ParameterMode {
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub(crate) enum ParameterMode {
+    #[default]
     Position = 0,
     Immediate = 1,
     Relative = 2,
@@ -441,6 +740,16 @@ impl TryFrom<u8> for ParameterMode {
 pub(crate) enum Status {
     Halted,
     PausedForInput,
+    BudgetExhausted,
+    // The program is in a loop without I/O that it can never leave.
+    Livelocked,
+}
+
+#[derive(Debug, Eq, PartialEq)]
+pub(crate) enum Step {
+    Output(i64),
+    NeedInput,
+    Halted,
 }
 
 #[derive(Debug)]
@@ -449,3 +758,57 @@ pub(crate) struct RunOutput {
     pub(crate) status: Status,
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FAR: i64 = i64::MAX;
+
+    #[allow(clippy::needless_return)]
+    fn overflow(ip: u64, opcode: Opcode) -> ComputerError {
+        return ComputerError::AddressOverflow { ip, rel_base: 1, opcode, offset: FAR };
+    }
+
+    #[test]
+    fn relative_reads_overflow() {
+        let mut computer = Computer::from_program(&[109, 1, 204, FAR, 99]);
+        let err = computer.run(std::iter::empty()).unwrap_err();
+        assert_eq!(err, overflow(2, Opcode::Output));
+    }
+
+    #[test]
+    fn relative_writes_overflow() {
+        let mut computer = Computer::from_program(&[109, 1, 203, FAR, 99]);
+        let err = computer.run([7].into_iter()).unwrap_err();
+        assert_eq!(err, overflow(2, Opcode::Input));
+    }
+
+    #[test]
+    fn relative_param_addrs_overflow() {
+        let computer = Computer::from_program(&[109, 1, 22201, 3, FAR, -4, 99]);
+        let decoded = decode(&computer.memory, 2).unwrap();
+        assert_eq!(decoded.param_addr(2, 0, 1), Ok(Some(4)));
+        assert_eq!(decoded.param_addr(2, 1, 1), Err(overflow(2, Opcode::Add)));
+        assert_eq!(decoded.param_addr(2, 2, 1), Err(ComputerError::NegativeAddress {
+            ip: 2,
+            rel_base: 1,
+            opcode: Opcode::Add,
+            addr: -3,
+        }));
+    }
+
+    #[test]
+    fn rel_base_overflows() {
+        let mut computer = Computer::from_program(&[109, FAR, 109, 1, 99]);
+        let err = computer.run(std::iter::empty()).unwrap_err();
+        assert_eq!(err, ComputerError::Overflow {
+            ip: 2,
+            rel_base: FAR,
+            opcode: Opcode::AdjRelBase,
+            a: FAR,
+            b: 1,
+        });
+        assert_eq!(computer.rel_base, FAR);
+    }
+}
@@ -0,0 +1,96 @@
+#![allow(clippy::needless_return)]
+
+use anyhow::{Result, bail};
+
+use super::{Computer, Step};
+
+// Talks to programs that read and print ASCII text, one character per value.
+// A value outside the ASCII range is taken as the program's answer, e.g. the
+// amount of dust collected by day 17's robot.
+pub(crate) struct AsciiComputer {
+    pub(crate) computer: Computer,
+    // Line printed so far, without its newline.
+    line: String,
+    answer: Option<i64>,
+    halted: bool,
+}
+
+impl AsciiComputer {
+    pub(crate) fn new(computer: Computer) -> Self {
+        return AsciiComputer {
+            computer,
+            line: String::new(),
+            answer: None,
+            halted: false,
+        };
+    }
+
+    pub(crate) fn send_line(&mut self, line: &str) -> Result<()> {
+        if let Some(ch) = line.chars().find(|ch| !ch.is_ascii() || *ch == '\n') {
+            bail!("Can't send {ch:?} as part of a line");
+        }
+
+        for ch in line.chars().chain(std::iter::once('\n')) {
+            self.computer.push_input(ch as i64);
+        }
+
+        return Ok(());
+    }
+
+    // Runs until the program waits for input or halts and returns the lines
+    // it printed in the meantime. A prompt that doesn't end with a newline
+    // still comes back as the last line.
+    pub(crate) fn read_until_prompt(&mut self) -> Result<Vec<String>> {
+        let mut lines = Vec::new();
+        loop {
+            match self.computer.step()? {
+                Step::Output(val) => {
+                    if let Some(line) = self.print(val)? {
+                        lines.push(line);
+                    }
+                },
+                Step::NeedInput => break,
+                Step::Halted => {
+                    self.halted = true;
+                    break;
+                },
+            }
+        }
+
+        if !self.line.is_empty() {
+            lines.push(std::mem::take(&mut self.line));
+        }
+
+        return Ok(lines);
+    }
+
+    pub(crate) fn answer(&self) -> Option<i64> {
+        return self.answer;
+    }
+
+    pub(crate) fn halted(&self) -> bool {
+        return self.halted;
+    }
+
+    // Returns the line completed by `val`, if any.
+    fn print(&mut self, val: i64) -> Result<Option<String>> {
+        let ch = match u8::try_from(val) {
+            Ok(byte) if byte.is_ascii() => byte as char,
+            _ => {
+                if let Some(answer) = self.answer {
+                    bail!("Expected ASCII output after the answer {answer}, got {val}");
+                }
+
+                self.answer = Some(val);
+                return Ok(None);
+            },
+        };
+
+        if ch == '\n' {
+            return Ok(Some(std::mem::take(&mut self.line)));
+        }
+
+        self.line.push(ch);
+        return Ok(None);
+    }
+}
@@ -0,0 +1,407 @@
+#![allow(clippy::needless_return)]
+
+use std::{collections::HashMap, fs};
+
+use anyhow::{Context, Result, anyhow, bail};
+
+use super::{Computer, Opcode, ParameterMode};
+
+// Source format, one statement per line:
+//
+//     loop:   IN [x]              ; position mode, same as `IN x`
+//             ADD [x], #-1, rb+2  ; immediate and relative modes
+//             JT [x], #loop       ; labels may be used before they're defined
+//             HLT
+//     x:      .data 0, loop+1
+//     msg:    .string "hi\n"
+//
+// Everything after `;` is a comment.
+pub(crate) fn assemble(source: &str) -> Result<Vec<i64>> {
+    let mut labels = HashMap::new();
+    let mut statements = Vec::new();
+    let mut addr = 0;
+
+    for (idx, line) in source.lines().enumerate() {
+        let line_no = idx + 1;
+        let mut rest = strip_comment(line).trim();
+
+        while let Some((label, tail)) = split_label(rest) {
+            if labels.insert(label.to_string(), addr as i64).is_some() {
+                bail!("Line {line_no}: label `{label}` is defined more than once");
+            }
+            rest = tail.trim();
+        }
+
+        if rest.is_empty() {
+            continue;
+        }
+
+        let statement = parse_statement(rest)
+            .with_context(|| format!("Line {line_no}: failed to parse `{rest}`"))?;
+        addr += statement.size();
+        statements.push((line_no, statement));
+    }
+
+    let mut program = Vec::new();
+    for (line_no, statement) in statements {
+        statement.emit(&labels, &mut program)
+            .with_context(|| format!("Line {line_no}: failed to assemble"))?;
+    }
+
+    return Ok(program);
+}
+
+pub(crate) fn assemble_computer(source: &str) -> Result<Computer> {
+    let program = assemble(source)?;
+    return Ok(Computer::from_program(&program));
+}
+
+pub(crate) fn assemble_file(source_file: &str, output_file: &str) -> Result<()> {
+    let source = fs::read_to_string(source_file)
+        .context("Couldn't read from the source file")?;
+    let program = assemble(&source)?;
+    return write_program(output_file, &program);
+}
+
+pub(crate) fn write_program(output_file: &str, program: &[i64]) -> Result<()> {
+    let contents = program.iter()
+        .map(|val| val.to_string())
+        .collect::<Vec<_>>()
+        .join(",");
+    fs::write(output_file, contents + "\n")
+        .context("Couldn't write to the output file")?;
+
+    return Ok(());
+}
+
+// Loads either assembler source (`.asm`) or a comma-separated program.
+pub(crate) fn load(input_file: &str) -> Result<Computer> {
+    if input_file.ends_with(".asm") {
+        let source = fs::read_to_string(input_file)
+            .context("Couldn't read from the source file")?;
+        return assemble_computer(&source);
+    }
+
+    return Computer::from_file(input_file);
+}
+
+enum Statement {
+    Instruction {
+        opcode: Opcode,
+        operands: Vec<Operand>,
+    },
+    Data(Vec<Expr>),
+    String(Vec<i64>),
+}
+
+struct Operand {
+    mode: ParameterMode,
+    expr: Expr,
+}
+
+enum Expr {
+    Number(i64),
+    Label {
+        name: String,
+        offset: i64,
+    },
+}
+
+impl Statement {
+    fn size(&self) -> u64 {
+        return match self {
+            Statement::Instruction { opcode, .. } => opcode.size(),
+            Statement::Data(values) => values.len() as u64,
+            Statement::String(chars) => chars.len() as u64,
+        };
+    }
+
+    fn emit(&self, labels: &HashMap<String, i64>, program: &mut Vec<i64>) -> Result<()> {
+        match self {
+            Statement::Instruction { opcode, operands } => {
+                let mut word = opcode.code() as i64;
+                let mut factor = 100;
+                for operand in operands {
+                    word += operand.mode as i64 * factor;
+                    factor *= 10;
+                }
+                program.push(word);
+
+                for operand in operands {
+                    program.push(operand.expr.resolve(labels)?);
+                }
+            },
+            Statement::Data(values) => {
+                for val in values {
+                    program.push(val.resolve(labels)?);
+                }
+            },
+            Statement::String(chars) => {
+                program.extend(chars);
+            },
+        }
+
+        return Ok(());
+    }
+}
+
+impl Expr {
+    fn resolve(&self, labels: &HashMap<String, i64>) -> Result<i64> {
+        return match self {
+            Expr::Number(val) => Ok(*val),
+            Expr::Label { name, offset } => {
+                let addr = labels.get(name)
+                    .ok_or_else(|| anyhow!("Undefined label `{name}`"))?;
+                Ok(addr + offset)
+            },
+        };
+    }
+}
+
+fn strip_comment(line: &str) -> &str {
+    // `;` inside a string literal doesn't start a comment.
+    let mut in_string = false;
+    let mut escaped = false;
+    for (idx, ch) in line.char_indices() {
+        match ch {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            ';' if !in_string => return &line[..idx],
+            _ => {},
+        }
+    }
+
+    return line;
+}
+
+fn split_label(text: &str) -> Option<(&str, &str)> {
+    let (label, rest) = text.split_once(':')?;
+    if !is_identifier(label) {
+        return None;
+    }
+
+    return Some((label, rest));
+}
+
+fn is_identifier(text: &str) -> bool {
+    let mut chars = text.chars();
+    return match chars.next() {
+        Some(ch) if ch.is_ascii_alphabetic() || ch == '_' => {
+            chars.all(|ch| ch.is_ascii_alphanumeric() || ch == '_')
+        },
+        _ => false,
+    };
+}
+
+fn parse_statement(text: &str) -> Result<Statement> {
+    let (head, rest) = match text.split_once(char::is_whitespace) {
+        Some((head, rest)) => (head, rest.trim()),
+        None => (text, ""),
+    };
+
+    if head == ".data" {
+        let values = split_operands(rest)
+            .map(parse_expr)
+            .collect::<Result<Vec<_>>>()?;
+        if values.is_empty() {
+            bail!("`.data` needs at least one value");
+        }
+        return Ok(Statement::Data(values));
+    }
+
+    if head == ".string" {
+        return Ok(Statement::String(parse_string(rest)?));
+    }
+
+    let opcode = Opcode::from_mnemonic(head)
+        .ok_or_else(|| anyhow!("Unknown mnemonic `{head}`"))?;
+    let operands = split_operands(rest)
+        .map(parse_operand)
+        .collect::<Result<Vec<_>>>()?;
+
+    if operands.len() as u64 != opcode.arity() {
+        bail!(
+            "`{}` takes {} operand(s), got {}",
+            opcode.mnemonic(),
+            opcode.arity(),
+            operands.len(),
+        );
+    }
+
+    for (idx, operand) in operands.iter().enumerate() {
+        if opcode.is_write_param(idx) && operand.mode == ParameterMode::Immediate {
+            bail!("Operand {} of `{}` is written to and can't be immediate",
+                idx + 1, opcode.mnemonic());
+        }
+    }
+
+    return Ok(Statement::Instruction { opcode, operands });
+}
+
+fn split_operands(text: &str) -> impl Iterator<Item=&str> {
+    return text.split(",")
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty());
+}
+
+fn parse_operand(text: &str) -> Result<Operand> {
+    if let Some(rest) = text.strip_prefix('#') {
+        return Ok(Operand {
+            mode: ParameterMode::Immediate,
+            expr: parse_expr(rest)?,
+        });
+    }
+
+    if let Some(rest) = text.strip_prefix("rb")
+            .map(|rest| rest.trim_start())
+            .filter(|rest| rest.starts_with(['+', '-'])) {
+        let offset = match rest.strip_prefix('+') {
+            Some(rest) => rest.trim().parse::<i64>(),
+            None => rest.replace(' ', "").parse::<i64>(),
+        };
+        let offset = offset
+            .with_context(|| format!("Invalid relative offset in `{text}`"))?;
+
+        return Ok(Operand {
+            mode: ParameterMode::Relative,
+            expr: Expr::Number(offset),
+        });
+    }
+
+    let inner = text.strip_prefix('[')
+        .and_then(|rest| rest.strip_suffix(']'))
+        .unwrap_or(text);
+
+    return Ok(Operand {
+        mode: ParameterMode::Position,
+        expr: parse_expr(inner)?,
+    });
+}
+
+// Either a number or `label`, `label+N`, `label-N`.
+fn parse_expr(text: &str) -> Result<Expr> {
+    let text = text.trim();
+    if let Ok(val) = text.parse::<i64>() {
+        return Ok(Expr::Number(val));
+    }
+
+    let (name, offset) = match text.find(['+', '-']) {
+        Some(idx) => {
+            let offset = text[idx..].replace(' ', "");
+            let offset = offset.strip_prefix('+').unwrap_or(&offset);
+            let offset = offset.parse::<i64>()
+                .with_context(|| format!("Invalid offset in `{text}`"))?;
+            (text[..idx].trim(), offset)
+        },
+        None => (text, 0),
+    };
+
+    if !is_identifier(name) {
+        bail!("Invalid value `{text}`");
+    }
+
+    return Ok(Expr::Label { name: name.to_string(), offset });
+}
+
+fn parse_string(text: &str) -> Result<Vec<i64>> {
+    let inner = text.strip_prefix('"')
+        .and_then(|rest| rest.strip_suffix('"'))
+        .ok_or_else(|| anyhow!("`.string` needs a double-quoted literal"))?;
+
+    let mut res = Vec::new();
+    let mut chars = inner.chars();
+    while let Some(ch) = chars.next() {
+        let ch = if ch == '\\' {
+            match chars.next() {
+                Some('n') => '\n',
+                Some('t') => '\t',
+                Some('0') => '\0',
+                Some('\\') => '\\',
+                Some('"') => '"',
+                Some(c) => bail!("Unknown escape `\\{c}`"),
+                None => bail!("Unterminated escape at the end of the string"),
+            }
+        } else {
+            ch
+        };
+        res.push(ch as i64);
+    }
+
+    return Ok(res);
+}
+
+pub(crate) fn command(args: &[String]) -> Result<()> {
+    if args.len() < 2 {
+        bail!("Usage: asm <source_file> <output_file>");
+    }
+
+    return assemble_file(&args[0], &args[1]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::computer::Status;
+
+    #[test]
+    fn labels_and_forward_references() {
+        let source = "
+                    IN [n]
+            loop:   OUT [n]             ; counts down from the input
+                    ADD [n], #-1, [n]
+                    JT [n], #loop
+                    HLT
+            n:      .data 0
+        ";
+        assert_eq!(
+            assemble(source).unwrap(),
+            vec![3, 12, 4, 12, 1001, 12, -1, 12, 1005, 12, 2, 99, 0],
+        );
+
+        let res = assemble_computer(source).unwrap().run([3].into_iter()).unwrap();
+        assert_eq!(res.outputs, vec![3, 2, 1]);
+        assert_eq!(res.status, Status::Halted);
+    }
+
+    #[test]
+    fn data_and_strings() {
+        let source = r#"
+                    HLT
+            a:      .data 7, -3, b, b+1, a-1
+            b:      .string "a;\"\n"
+        "#;
+        assert_eq!(
+            assemble(source).unwrap(),
+            vec![99, 7, -3, 6, 7, 0, 'a' as i64, ';' as i64, '"' as i64, '\n' as i64],
+        );
+    }
+
+    #[test]
+    fn parameter_modes() {
+        assert_eq!(assemble("ADD #1, [5], rb+2").unwrap(), vec![20101, 1, 5, 2]);
+        assert_eq!(assemble("MUL rb - 3, 4, rb+0").unwrap(), vec![20202, -3, 4, 0]);
+
+        // Prints the second character through a relative read.
+        let source = r#"
+                    ARB #msg
+                    OUT rb+1
+                    HLT
+            msg:    .string "hi"
+        "#;
+        assert_eq!(assemble(source).unwrap(), vec![109, 5, 204, 1, 99, 104, 105]);
+
+        let res = assemble_computer(source).unwrap().run(std::iter::empty()).unwrap();
+        assert_eq!(res.outputs, vec!['i' as i64]);
+        assert_eq!(res.status, Status::Halted);
+    }
+
+    #[test]
+    fn errors() {
+        assert!(assemble("JT #1, #nowhere").is_err());
+        assert!(assemble("ADD #1, #2, #3").is_err());
+        assert!(assemble("OUT #1, #2").is_err());
+        assert!(assemble("x: .data 0\nx: .data 1").is_err());
+        assert!(assemble("FOO 1").is_err());
+    }
+}
@@ -0,0 +1,213 @@
+#![allow(clippy::needless_return)]
+
+use std::{hint::black_box, time::{Duration, Instant}};
+
+use anyhow::{Context, Result, anyhow, bail};
+
+use super::{
+    Computer, ComputerError,
+    memory::{MemoryBackend, set_default_backend, set_default_decode_cache},
+};
+
+// VM configurations compared by `bench`, each one set up process-wide before
+// the program is loaded.
+struct Variant {
+    name: &'static str,
+    apply: fn(),
+}
+
+const VARIANTS: &[Variant] = &[
+    Variant {
+        name: "sparse memory",
+        apply: || {
+            set_default_backend(MemoryBackend::Sparse);
+            set_default_decode_cache(false);
+        },
+    },
+    Variant {
+        name: "dense memory",
+        apply: || {
+            set_default_backend(MemoryBackend::Dense);
+            set_default_decode_cache(false);
+        },
+    },
+    Variant {
+        name: "dense memory, cached",
+        apply: || {
+            set_default_backend(MemoryBackend::Dense);
+            set_default_decode_cache(true);
+        },
+    },
+];
+
+// What a day does with its VM, minus parsing the input and printing. Returns
+// a checksum of the outputs, so variants can be checked to agree.
+struct Workload {
+    day: u32,
+    run: fn(&Computer) -> std::result::Result<i64, ComputerError>,
+}
+
+const WORKLOADS: &[Workload] = &[
+    Workload { day: 2, run: noun_verb_search },
+    Workload { day: 7, run: amplifier_chains },
+    Workload { day: 9, run: boost_sensor },
+    Workload { day: 13, run: arcade_screen },
+    Workload { day: 19, run: beam_probes },
+];
+
+// Cuts off bad nouns and verbs in day 2, which may loop forever.
+const BUDGET: u64 = 1_000_000;
+
+// Day 2 part 2, trying every noun and verb instead of stopping at the answer.
+fn noun_verb_search(computer: &Computer) -> std::result::Result<i64, ComputerError> {
+    let mut sum = 0i64;
+    for noun in 0..=99 {
+        for verb in 0..=99 {
+            let mut c = computer.clone();
+            c.memory.write(1, noun);
+            c.memory.write(2, verb);
+            // Faults are as much a result as anything else here.
+            if c.run_with_budget(std::iter::empty(), BUDGET).is_ok() {
+                sum = sum.wrapping_add(c.memory.read(0));
+            }
+        }
+    }
+
+    return Ok(sum);
+}
+
+// Day 7 part 1, every phase setting through the five amplifiers.
+fn amplifier_chains(computer: &Computer) -> std::result::Result<i64, ComputerError> {
+    let mut best = i64::MIN;
+    for phases in permutations(&[0, 1, 2, 3, 4]) {
+        let mut signal = 0;
+        for phase in phases {
+            let output = computer.clone().run([phase, signal].into_iter())?;
+            signal = output.outputs.last().copied().unwrap_or(0);
+        }
+        best = best.max(signal);
+    }
+
+    return Ok(best);
+}
+
+// Day 9 part 2, the long-running one.
+fn boost_sensor(computer: &Computer) -> std::result::Result<i64, ComputerError> {
+    let output = computer.clone().run([2].into_iter())?;
+    return Ok(checksum(&output.outputs));
+}
+
+// Day 13 part 1, drawing the screen without playing.
+fn arcade_screen(computer: &Computer) -> std::result::Result<i64, ComputerError> {
+    let output = computer.clone().run(std::iter::empty())?;
+    return Ok(checksum(&output.outputs));
+}
+
+// Day 19 part 1, one fresh VM per probed point.
+fn beam_probes(computer: &Computer) -> std::result::Result<i64, ComputerError> {
+    let mut sum = 0;
+    for y in 0..50 {
+        for x in 0..50 {
+            let output = computer.clone().run([x, y].into_iter())?;
+            sum += checksum(&output.outputs);
+        }
+    }
+
+    return Ok(sum);
+}
+
+fn checksum(outputs: &[i64]) -> i64 {
+    return outputs.iter().fold(0i64, |acc, val| acc.wrapping_mul(31).wrapping_add(*val));
+}
+
+fn permutations(items: &[i64]) -> Vec<Vec<i64>> {
+    if items.is_empty() {
+        return vec![vec![]];
+    }
+
+    let mut res = Vec::new();
+    for (idx, first) in items.iter().enumerate() {
+        let mut rest = items.to_vec();
+        rest.remove(idx);
+        for mut tail in permutations(&rest) {
+            tail.insert(0, *first);
+            res.push(tail);
+        }
+    }
+
+    return res;
+}
+
+// Times every workload whose input is in `input_dir` as `day<n>.txt`, on a VM
+// loaded up front for each variant.
+pub(crate) fn command(args: &[String]) -> Result<()> {
+    let usage = "Usage: bench <input_dir> [--days 2,7,9,13,19] [--runs n]";
+
+    let mut positional = Vec::new();
+    let mut runs = 3;
+    let mut days = WORKLOADS.iter().map(|w| w.day).collect::<Vec<_>>();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--runs" => {
+                let value = args.next().ok_or_else(|| anyhow!("Missing value for `--runs`"))?;
+                runs = value.parse::<u32>().context("Invalid number of runs")?;
+            },
+            "--days" => {
+                let value = args.next().ok_or_else(|| anyhow!("Missing value for `--days`"))?;
+                days = value.split(',')
+                    .map(|day| day.trim().parse::<u32>())
+                    .collect::<std::result::Result<Vec<_>, _>>()
+                    .context("Invalid list of days")?;
+            },
+            _ => positional.push(arg.clone()),
+        }
+    }
+
+    if positional.len() != 1 || runs == 0 {
+        bail!(usage);
+    }
+    let input_dir = &positional[0];
+
+    let mut workloads = Vec::new();
+    for day in days {
+        let workload = WORKLOADS.iter()
+            .find(|w| w.day == day)
+            .ok_or_else(|| anyhow!("No benchmark for day {day}"))?;
+        workloads.push(workload);
+    }
+
+    for workload in workloads {
+        let input_file = format!("{input_dir}/day{}.txt", workload.day);
+
+        println!("day {}", workload.day);
+        let mut expected = None;
+        for variant in VARIANTS {
+            (variant.apply)();
+            let computer = Computer::from_file(&input_file)
+                .with_context(|| format!("Couldn't load `{input_file}`"))?;
+
+            let mut timings = Vec::new();
+            for _ in 0..runs {
+                let start = Instant::now();
+                let res = (workload.run)(black_box(&computer))
+                    .with_context(|| format!("Day {} failed with {}", workload.day, variant.name))?;
+                timings.push(start.elapsed());
+
+                if *expected.get_or_insert(res) != res {
+                    bail!("Day {} gave a different result with {}", workload.day, variant.name);
+                }
+            }
+
+            let min = timings.iter().min().unwrap();
+            let mean = timings.iter().sum::<Duration>() / timings.len() as u32;
+            println!("  {:<24} min {:>10.3} ms, mean {:>10.3} ms",
+                variant.name,
+                min.as_secs_f64() * 1000.0,
+                mean.as_secs_f64() * 1000.0);
+        }
+    }
+
+    return Ok(());
+}
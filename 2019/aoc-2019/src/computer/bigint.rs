@@ -0,0 +1,214 @@
+#![allow(clippy::needless_return)]
+
+use std::{cmp::Ordering, fmt::Display, ops::{Add, Mul}, str::FromStr};
+
+use anyhow::{anyhow, bail};
+
+// Arbitrary-precision integer, only as much of one as Intcode arithmetic
+// needs. The magnitude is stored as little-endian 32-bit limbs without
+// trailing zero limbs, so zero has no limbs and is never negative.
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub(crate) struct BigInt {
+    negative: bool,
+    mag: Vec<u32>,
+}
+
+// Largest power of ten fitting in a limb, used for decimal conversion.
+const DECIMAL_BASE: u64 = 1_000_000_000;
+
+impl BigInt {
+    fn from_parts(negative: bool, mut mag: Vec<u32>) -> Self {
+        while mag.last() == Some(&0) {
+            mag.pop();
+        }
+        let negative = negative && !mag.is_empty();
+
+        return BigInt { negative, mag };
+    }
+
+    pub(crate) fn to_i64(&self) -> Option<i64> {
+        if self.mag.len() > 2 {
+            return None;
+        }
+
+        let mag = self.low_u64();
+        if self.negative {
+            return (mag <= i64::MIN.unsigned_abs()).then(|| (mag as i64).wrapping_neg());
+        }
+        return i64::try_from(mag).ok();
+    }
+
+    // Low 64 bits in two's complement, i.e. what wrapping arithmetic would
+    // have produced.
+    pub(crate) fn wrapping_i64(&self) -> i64 {
+        let low = self.low_u64() as i64;
+        if self.negative {
+            return low.wrapping_neg();
+        }
+        return low;
+    }
+
+    fn low_u64(&self) -> u64 {
+        let lo = self.mag.first().copied().unwrap_or(0) as u64;
+        let hi = self.mag.get(1).copied().unwrap_or(0) as u64;
+        return hi << 32 | lo;
+    }
+}
+
+impl From<i64> for BigInt {
+    fn from(value: i64) -> Self {
+        let mag = value.unsigned_abs();
+        return BigInt::from_parts(value < 0, vec![mag as u32, (mag >> 32) as u32]);
+    }
+}
+
+impl Add for &BigInt {
+    type Output = BigInt;
+
+    fn add(self, other: &BigInt) -> BigInt {
+        if self.negative == other.negative {
+            return BigInt::from_parts(self.negative, add_mag(&self.mag, &other.mag));
+        }
+
+        return match cmp_mag(&self.mag, &other.mag) {
+            Ordering::Less => BigInt::from_parts(other.negative, sub_mag(&other.mag, &self.mag)),
+            _ => BigInt::from_parts(self.negative, sub_mag(&self.mag, &other.mag)),
+        };
+    }
+}
+
+impl Mul for &BigInt {
+    type Output = BigInt;
+
+    fn mul(self, other: &BigInt) -> BigInt {
+        let mut mag = vec![0u32; self.mag.len() + other.mag.len()];
+        for (i, &x) in self.mag.iter().enumerate() {
+            let mut carry = 0u64;
+            for (j, &y) in other.mag.iter().enumerate() {
+                let cur = mag[i + j] as u64 + x as u64 * y as u64 + carry;
+                mag[i + j] = cur as u32;
+                carry = cur >> 32;
+            }
+            mag[i + other.mag.len()] = carry as u32;
+        }
+
+        return BigInt::from_parts(self.negative != other.negative, mag);
+    }
+}
+
+impl Ord for BigInt {
+    fn cmp(&self, other: &Self) -> Ordering {
+        return match (self.negative, other.negative) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => cmp_mag(&self.mag, &other.mag),
+            (true, true) => cmp_mag(&other.mag, &self.mag),
+        };
+    }
+}
+
+impl PartialOrd for BigInt {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        return Some(self.cmp(other));
+    }
+}
+
+impl Display for BigInt {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.mag.is_empty() {
+            return write!(f, "0");
+        }
+
+        // Base 10^9 digits, least significant first.
+        let mut mag = self.mag.clone();
+        let mut chunks = Vec::new();
+        while !mag.is_empty() {
+            let mut rem = 0u64;
+            for limb in mag.iter_mut().rev() {
+                let cur = rem << 32 | *limb as u64;
+                *limb = (cur / DECIMAL_BASE) as u32;
+                rem = cur % DECIMAL_BASE;
+            }
+            while mag.last() == Some(&0) {
+                mag.pop();
+            }
+            chunks.push(rem);
+        }
+
+        let mut text = if self.negative { String::from("-") } else { String::new() };
+        let mut chunks = chunks.iter().rev();
+        text += &chunks.next().unwrap().to_string();
+        for chunk in chunks {
+            text += &format!("{chunk:09}");
+        }
+
+        return write!(f, "{text}");
+    }
+}
+
+impl FromStr for BigInt {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let (negative, digits) = match s.strip_prefix("-") {
+            Some(digits) => (true, digits),
+            None => (false, s),
+        };
+        if digits.is_empty() {
+            bail!("Invalid integer `{s}`");
+        }
+
+        let mut mag = Vec::new();
+        for ch in digits.chars() {
+            let digit = ch.to_digit(10)
+                .ok_or_else(|| anyhow!("Invalid integer `{s}`"))?;
+
+            let mut carry = digit as u64;
+            for limb in mag.iter_mut() {
+                let cur = *limb as u64 * 10 + carry;
+                *limb = cur as u32;
+                carry = cur >> 32;
+            }
+            if carry != 0 {
+                mag.push(carry as u32);
+            }
+        }
+
+        return Ok(BigInt::from_parts(negative, mag));
+    }
+}
+
+fn cmp_mag(a: &[u32], b: &[u32]) -> Ordering {
+    return a.len().cmp(&b.len())
+        .then_with(|| a.iter().rev().cmp(b.iter().rev()));
+}
+
+fn add_mag(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut mag = Vec::with_capacity(a.len().max(b.len()) + 1);
+    let mut carry = 0u64;
+    for idx in 0..a.len().max(b.len()) {
+        let cur = *a.get(idx).unwrap_or(&0) as u64 + *b.get(idx).unwrap_or(&0) as u64 + carry;
+        mag.push(cur as u32);
+        carry = cur >> 32;
+    }
+    mag.push(carry as u32);
+
+    return mag;
+}
+
+// Expects `a >= b`.
+fn sub_mag(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut mag = Vec::with_capacity(a.len());
+    let mut borrow = 0i64;
+    for (idx, &x) in a.iter().enumerate() {
+        let mut cur = x as i64 - *b.get(idx).unwrap_or(&0) as i64 - borrow;
+        borrow = 0;
+        if cur < 0 {
+            cur += 1 << 32;
+            borrow = 1;
+        }
+        mag.push(cur as u32);
+    }
+
+    return mag;
+}
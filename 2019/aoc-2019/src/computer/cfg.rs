@@ -0,0 +1,264 @@
+#![allow(clippy::needless_return)]
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use anyhow::{Result, bail};
+
+use super::{Decoded, Memory, Opcode, ParameterMode, disasm::{self, Line}};
+
+// Control-flow graph of a program, found by following every jump with an
+// immediate target from address 0. Code that's only reachable through
+// indirect jumps, or that gets written at runtime, isn't in it.
+//
+// Calls follow the convention of the puzzle programs: the return address is
+// stored in a `rel_base` cell right before jumping to the function, which
+// returns by jumping to the address in that cell.
+#[derive(Debug)]
+pub(crate) struct Cfg {
+    pub(crate) blocks: BTreeMap<u64, Block>,
+}
+
+#[derive(Debug)]
+pub(crate) struct Block {
+    pub(crate) start: u64,
+    pub(crate) instructions: Vec<(u64, Decoded)>,
+    pub(crate) exit: Exit,
+}
+
+// How control leaves a block.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum Exit {
+    // Falls through into the block starting at the next instruction.
+    Next(u64),
+    Jump(u64),
+    // Jump to a function that comes back to `ret`.
+    Call {
+        target: u64,
+        ret: u64,
+    },
+    // Conditional jump, the last instruction of the block decides.
+    Branch {
+        target: u64,
+        next: u64,
+    },
+    // Jump to an address read from memory. Execution continues at `next`
+    // if the jump isn't taken.
+    Indirect {
+        next: Option<u64>,
+    },
+    // Unconditional jump to an address in a `rel_base` cell.
+    Return,
+    Halt,
+    // Runs into something that isn't a valid instruction.
+    Invalid,
+}
+
+impl Exit {
+    pub(crate) fn successors(&self) -> Vec<u64> {
+        return match *self {
+            Exit::Next(next) | Exit::Jump(next) => vec![next],
+            Exit::Call { target, ret } => vec![target, ret],
+            Exit::Branch { target, next } => vec![target, next],
+            Exit::Indirect { next } => next.into_iter().collect(),
+            Exit::Return | Exit::Halt | Exit::Invalid => vec![],
+        };
+    }
+
+    // Successors within the same function, i.e. without entering calls.
+    pub(crate) fn local_successors(&self) -> Vec<u64> {
+        return match *self {
+            Exit::Call { ret, .. } => vec![ret],
+            _ => self.successors(),
+        };
+    }
+}
+
+// Value stored by `ADD #v, #0, rb+n` or `MUL #v, #1, rb+n` and the like,
+// i.e. what storing a return address looks like.
+pub(crate) fn relative_store(decoded: &Decoded) -> Option<i64> {
+    let [a, b, dst] = decoded.params;
+    if dst.mode != ParameterMode::Relative
+            || a.mode != ParameterMode::Immediate
+            || b.mode != ParameterMode::Immediate {
+        return None;
+    }
+
+    let neutral = match decoded.opcode {
+        Opcode::Add => 0,
+        Opcode::Mul => 1,
+        _ => return None,
+    };
+    return match (a.val, b.val) {
+        (v, n) if n == neutral => Some(v),
+        (n, v) if n == neutral => Some(v),
+        _ => None,
+    };
+}
+
+// Where control can go after the instruction at `addr`, `None` when it just
+// carries on with the next one. `stored` are the values `relative_store`
+// found earlier in the same straight line of code.
+fn instruction_exit(addr: u64, decoded: &Decoded, stored: &[i64]) -> Option<Exit> {
+    let next = addr + decoded.size();
+    let [cond, target, _] = decoded.params;
+
+    let jump_if = match decoded.opcode {
+        Opcode::JumpIfTrue => true,
+        Opcode::JumpIfFalse => false,
+        Opcode::Halt => return Some(Exit::Halt),
+        _ => return None,
+    };
+
+    // An immediate condition makes the jump either unconditional or a no-op.
+    let always = cond.mode == ParameterMode::Immediate && (cond.val != 0) == jump_if;
+    let never = cond.mode == ParameterMode::Immediate && !always;
+    if never {
+        return Some(Exit::Next(next));
+    }
+
+    let exit = match (target.mode, always) {
+        (ParameterMode::Immediate, _) if target.val < 0 => Exit::Invalid,
+        (ParameterMode::Immediate, true) if stored.contains(&(next as i64)) => {
+            Exit::Call { target: target.val as u64, ret: next }
+        },
+        (ParameterMode::Immediate, true) => Exit::Jump(target.val as u64),
+        (ParameterMode::Relative, true) => Exit::Return,
+        (ParameterMode::Immediate, false) => Exit::Branch { target: target.val as u64, next },
+        (_, true) => Exit::Indirect { next: None },
+        (_, false) => Exit::Indirect { next: Some(next) },
+    };
+
+    return Some(exit);
+}
+
+fn decode_at(memory: &Memory, addr: u64, len: u64) -> Option<Decoded> {
+    return match disasm::disassemble_line(memory, addr, len) {
+        Line::Instruction { decoded, .. } => Some(decoded),
+        Line::Data { .. } => None,
+    };
+}
+
+pub(crate) fn build(memory: &Memory) -> Cfg {
+    let len = memory.len();
+
+    // Every reachable instruction, and where blocks have to start.
+    let mut reachable = BTreeSet::new();
+    let mut leaders = BTreeSet::from([0]);
+    let mut pending = vec![(0, Vec::new())];
+    while let Some((addr, mut stored)) = pending.pop() {
+        if !reachable.insert(addr) {
+            continue;
+        }
+        let Some(decoded) = decode_at(memory, addr, len) else {
+            continue;
+        };
+
+        match instruction_exit(addr, &decoded, &stored) {
+            None => {
+                stored.extend(relative_store(&decoded));
+                pending.push((addr + decoded.size(), stored));
+            },
+            Some(exit) => {
+                let successors = exit.successors();
+                leaders.extend(successors.iter().copied());
+                pending.extend(successors.into_iter().map(|s| (s, Vec::new())));
+            },
+        }
+    }
+
+    let mut blocks = BTreeMap::new();
+    for &start in &leaders {
+        let mut instructions = Vec::new();
+        let mut stored = Vec::new();
+        let mut addr = start;
+        let exit = loop {
+            let Some(decoded) = decode_at(memory, addr, len) else {
+                break Exit::Invalid;
+            };
+            instructions.push((addr, decoded));
+
+            if let Some(exit) = instruction_exit(addr, &decoded, &stored) {
+                break exit;
+            }
+            stored.extend(relative_store(&decoded));
+
+            addr += decoded.size();
+            if leaders.contains(&addr) {
+                break Exit::Next(addr);
+            }
+        };
+
+        blocks.insert(start, Block { start, instructions, exit });
+    }
+
+    return Cfg { blocks };
+}
+
+impl Cfg {
+    pub(crate) fn to_dot(&self, memory: &Memory) -> String {
+        let mut dot = String::from("digraph intcode {\n");
+        dot += "    node [shape=box, fontname=monospace];\n";
+
+        let mut unresolved = false;
+        for block in self.blocks.values() {
+            let mut label = String::new();
+            for (addr, _) in &block.instructions {
+                let text = disasm::line_at(memory, *addr).text();
+                label += &format!("{addr}: {text}\\l");
+            }
+            if block.exit == Exit::Invalid {
+                label += &format!("{}: invalid\\l", block.end());
+            }
+            dot += &format!("    b{} [label=\"{label}\"];\n", block.start);
+
+            match block.exit {
+                Exit::Next(next) | Exit::Jump(next) => {
+                    dot += &format!("    b{} -> b{next};\n", block.start);
+                },
+                Exit::Call { target, ret } => {
+                    dot += &format!("    b{} -> b{target} [label=\"call\", style=dashed];\n", block.start);
+                    dot += &format!("    b{} -> b{ret} [label=\"return\"];\n", block.start);
+                },
+                Exit::Branch { target, next } => {
+                    dot += &format!("    b{} -> b{target} [label=\"taken\"];\n", block.start);
+                    dot += &format!("    b{} -> b{next} [label=\"not taken\"];\n", block.start);
+                },
+                Exit::Indirect { next } => {
+                    unresolved = true;
+                    dot += &format!("    b{} -> unresolved [style=dashed];\n", block.start);
+                    if let Some(next) = next {
+                        dot += &format!("    b{} -> b{next} [label=\"not taken\"];\n", block.start);
+                    }
+                },
+                Exit::Return | Exit::Halt | Exit::Invalid => {},
+            }
+        }
+
+        if unresolved {
+            dot += "    unresolved [shape=ellipse, style=dashed, label=\"indirect\"];\n";
+        }
+        dot += "}\n";
+
+        return dot;
+    }
+}
+
+impl Block {
+    // One past the last instruction.
+    pub(crate) fn end(&self) -> u64 {
+        return self.instructions.last()
+            .map_or(self.start, |(addr, decoded)| addr + decoded.size());
+    }
+}
+
+pub(crate) fn command(args: &[String]) -> Result<()> {
+    if args.is_empty() {
+        bail!("Usage: cfg <input_file>");
+    }
+
+    let computer = super::asm::load(&args[0])?;
+    let cfg = build(&computer.memory);
+    print!("{}", cfg.to_dot(&computer.memory));
+
+    return Ok(());
+}
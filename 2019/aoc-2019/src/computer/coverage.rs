@@ -0,0 +1,187 @@
+#![allow(clippy::needless_return)]
+
+use std::{collections::{BTreeSet, HashMap, HashSet}, fs, sync::{Arc, Mutex}};
+
+use anyhow::{Context, Result, bail};
+
+use super::{
+    Computer, ComputerError, Event, Memory, Observer, attach_global_observer,
+    disasm::{self, Line},
+};
+
+// Which cells of a program were executed and which were accessed as data,
+// added up over every VM it's attached to. Meant for VMs running the same
+// program, e.g. the clones made by a single day.
+#[derive(Debug, Default)]
+pub(crate) struct Coverage {
+    // Memory of the first VM before it executed anything.
+    program: Option<Memory>,
+    // Execution count by instruction address.
+    executed: HashMap<u64, u64>,
+    // Every cell of an executed instruction, parameters included.
+    code: HashSet<u64>,
+    read: HashSet<u64>,
+    written: HashSet<u64>,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct Summary {
+    pub(crate) code: u64,
+    pub(crate) dead: u64,
+    pub(crate) data: u64,
+    pub(crate) modified: u64,
+    pub(crate) unused: u64,
+}
+
+impl Coverage {
+    // The program with every line annotated: `code` for executed
+    // instructions, `dead` for ones never executed nor accessed, `data` for
+    // cells only read or written, and `self-modified` for code that got
+    // written to.
+    pub(crate) fn listing(&self) -> (Vec<String>, Summary) {
+        let empty = Memory::new();
+        let program = self.program.as_ref().unwrap_or(&empty);
+        let len = program.len();
+
+        let mut lines = Vec::new();
+        let mut summary = Summary::default();
+
+        let mut addr = 0;
+        while addr < len {
+            let (line, tags) = self.annotate(program, addr, len, &mut summary);
+            addr += line.size();
+            lines.push(format!("{:<72} | {}", line.to_string(), tags.join(", ")));
+        }
+
+        // Cells past the end of the program, e.g. the stack.
+        let beyond = self.read.iter()
+            .chain(self.written.iter())
+            .filter(|a| **a >= len)
+            .collect::<BTreeSet<_>>();
+        for &addr in beyond {
+            let (line, tags) = self.annotate(program, addr, addr + 1, &mut summary);
+            lines.push(format!("{:<72} | {}", line.to_string(), tags.join(", ")));
+        }
+
+        return (lines, summary);
+    }
+
+    fn annotate(&self, program: &Memory, addr: u64, len: u64, summary: &mut Summary)
+            -> (Line, Vec<String>) {
+        let mut tags = Vec::new();
+
+        if let Some(count) = self.executed.get(&addr) {
+            let line = disasm::line_at(program, addr);
+            let modified = (addr..addr + line.size())
+                .filter(|a| self.written.contains(a))
+                .count() as u64;
+
+            tags.push(format!("code x{count}"));
+            if modified > 0 {
+                tags.push(String::from("self-modified"));
+                summary.modified += modified;
+            }
+            summary.code += line.size();
+
+            return (line, tags);
+        }
+
+        let line = Line::Data { addr, val: program.read(addr) };
+        let code = self.code.contains(&addr);
+        let read = self.read.contains(&addr);
+        let written = self.written.contains(&addr);
+        if code || read || written {
+            if code {
+                tags.push(String::from("code"));
+                summary.code += 1;
+            } else {
+                tags.push(String::from("data"));
+                summary.data += 1;
+            }
+            if read {
+                tags.push(String::from("read"));
+            }
+            if written {
+                tags.push(String::from(if code { "self-modified" } else { "written" }));
+                summary.modified += code as u64;
+            }
+
+            return (line, tags);
+        }
+
+        // Never touched. Anything decoding as an instruction whose cells
+        // weren't touched either is taken as dead code.
+        let line = disasm::disassemble_line(program, addr, len);
+        let untouched = (addr..addr + line.size()).all(|a| {
+            !self.executed.contains_key(&a)
+                && !self.code.contains(&a)
+                && !self.read.contains(&a)
+                && !self.written.contains(&a)
+        });
+        if matches!(line, Line::Instruction { .. }) && untouched {
+            tags.push(String::from("dead"));
+            summary.dead += line.size();
+            return (line, tags);
+        }
+
+        tags.push(String::from("unused"));
+        summary.unused += 1;
+        return (Line::Data { addr, val: program.read(addr) }, tags);
+    }
+}
+
+impl Observer for Coverage {
+    fn before_step(&mut self, computer: &mut Computer) -> std::result::Result<(), ComputerError> {
+        if self.program.is_none() {
+            self.program = Some(computer.memory.clone());
+        }
+
+        // Faults are reported by the VM itself once it gets to decoding.
+        if let Ok(decoded) = computer.memory.decode(computer.ip) {
+            for idx in 0..decoded.params().len() {
+                if decoded.opcode.is_write_param(idx) {
+                    continue;
+                }
+                if let Ok(Some(addr)) = decoded.param_addr(computer.ip, idx, computer.rel_base) {
+                    self.read.insert(addr);
+                }
+            }
+        }
+
+        return Ok(());
+    }
+
+    fn after_step(&mut self, _computer: &Computer, event: &Event) {
+        *self.executed.entry(event.ip).or_default() += 1;
+        self.code.extend(event.ip..event.ip + event.instruction.opcode.size());
+
+        if let Some(write) = event.write {
+            self.written.insert(write.addr);
+        }
+    }
+}
+
+pub(crate) fn command(args: &[String]) -> Result<()> {
+    if args.len() < 3 {
+        bail!("Usage: coverage <out_file> <day> <input_file> [part]");
+    }
+
+    let coverage = Arc::new(Mutex::new(Coverage::default()));
+    attach_global_observer(coverage.clone());
+
+    let res = crate::run_day_with_args(&args[1..]);
+
+    let (lines, summary) = coverage.lock().unwrap().listing();
+    fs::write(&args[0], lines.join("\n") + "\n")
+        .with_context(|| format!("Couldn't write listing `{}`", args[0]))?;
+
+    println!();
+    println!("Coverage:");
+    println!("  code cells:          {}", summary.code);
+    println!("  dead code cells:     {}", summary.dead);
+    println!("  data cells:          {}", summary.data);
+    println!("  self-modified:       {}", summary.modified);
+    println!("  unused cells:        {}", summary.unused);
+
+    return res;
+}
@@ -0,0 +1,373 @@
+#![allow(clippy::needless_return)]
+
+use std::{collections::{BTreeMap, BTreeSet}, io::{self, BufRead, Write}, sync::{Arc, Mutex}};
+
+use anyhow::{Context, Result, anyhow, bail};
+
+use super::{
+    Arithmetic, Computer, ComputerError, Event, Observer, Opcode, attach_global_observer,
+    decode, disasm,
+};
+
+const HELP: &str = "\
+Commands:
+  s, step [n]            execute n instructions (default 1)
+  c, continue            run until a breakpoint or watchpoint
+  o, next-output         run until the program produces an output
+  b, break <addr>        set a breakpoint on ip
+  d, delete <addr>       remove a breakpoint
+  w, watch <addr> [rw]   stop when memory at addr is read (r), written (w) or both
+  u, unwatch <addr>      remove a watchpoint
+  i, info                show ip, rel_base, breakpoints and watchpoints
+  x <addr> [len]         dump memory
+  rb [off] [len]         dump memory relative to rel_base
+  set <addr> <val>       write to memory
+  arith [mode]           show or set arithmetic: wrapping, checked or bigint
+  back [n]               undo the last n instructions (default 1)
+  rewind <addr>          go back to the last write to addr
+  save <path>            save a snapshot of the VM
+  load <path>            replace the VM with a saved snapshot
+  mark                   remember the current state for `diff`
+  diff [path]            show what changed since `mark` or a saved snapshot
+  l, list [n]            disassemble n instructions from ip
+  detach                 stop debugging and run freely
+  q, quit                abort the program
+  h, help                show this message";
+
+// Instructions kept per VM for stepping backwards.
+const HISTORY_LIMIT: usize = 1_000_000;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Watch {
+    Read,
+    Write,
+    ReadWrite,
+}
+
+impl Watch {
+    fn on_read(&self) -> bool {
+        return *self != Watch::Write;
+    }
+
+    fn on_write(&self) -> bool {
+        return *self != Watch::Read;
+    }
+}
+
+#[derive(Debug, Eq, PartialEq)]
+enum Mode {
+    Step(u64),
+    Continue,
+    NextOutput,
+    Detached,
+}
+
+pub(crate) struct Debugger {
+    breakpoints: BTreeSet<u64>,
+    watchpoints: BTreeMap<u64, Watch>,
+    mode: Mode,
+    // State remembered by `mark`.
+    mark: Option<Computer>,
+}
+
+impl Debugger {
+    pub(crate) fn new() -> Self {
+        return Debugger {
+            breakpoints: BTreeSet::new(),
+            watchpoints: BTreeMap::new(),
+            mode: Mode::Step(1),
+            mark: None,
+        };
+    }
+
+    fn stop_reason(&mut self, computer: &Computer) -> Option<String> {
+        if let Mode::Step(n) = self.mode {
+            if n <= 1 {
+                return Some("step".to_string());
+            }
+            self.mode = Mode::Step(n - 1);
+        }
+
+        if self.breakpoints.contains(&computer.ip) {
+            return Some(format!("breakpoint at {}", computer.ip));
+        }
+
+        if self.watchpoints.is_empty() {
+            return None;
+        }
+
+        let decoded = decode(&computer.memory, computer.ip).ok()?;
+        for idx in 0..decoded.params().len() {
+            let Ok(Some(addr)) = decoded.param_addr(computer.ip, idx, computer.rel_base) else {
+                continue;
+            };
+            let Some(watch) = self.watchpoints.get(&addr) else {
+                continue;
+            };
+
+            if decoded.opcode.is_write_param(idx) {
+                if watch.on_write() {
+                    return Some(format!("write to [{addr}]"));
+                }
+            } else if watch.on_read() {
+                return Some(format!("read of [{addr}]"));
+            }
+        }
+
+        return None;
+    }
+
+    fn prompt(&mut self, computer: &mut Computer) -> Result<()> {
+        let stdin = io::stdin();
+        loop {
+            print!("(dbg) ");
+            io::stdout().flush()?;
+
+            let mut line = String::new();
+            if stdin.lock().read_line(&mut line)? == 0 {
+                // End of input, nobody is left to drive the session.
+                self.mode = Mode::Detached;
+                return Ok(());
+            }
+
+            let words = line.split_whitespace().collect::<Vec<_>>();
+            if words.is_empty() {
+                continue;
+            }
+
+            match self.command(computer, &words) {
+                Ok(true) => return Ok(()),
+                Ok(false) => {},
+                Err(e) if e.is::<Quit>() => return Err(e),
+                Err(e) => println!("error: {e:#}"),
+            }
+        }
+    }
+
+    // Returns whether execution should resume.
+    fn command(&mut self, computer: &mut Computer, words: &[&str]) -> Result<bool> {
+        let arg = |idx: usize| -> Result<i64> {
+            let word = words.get(idx)
+                .ok_or_else(|| anyhow!("Missing argument, see `help`"))?;
+            return word.parse::<i64>()
+                .with_context(|| format!("`{word}` is not a number"));
+        };
+        let addr = |idx: usize| -> Result<u64> {
+            let val = arg(idx)?;
+            if val < 0 {
+                bail!("Address can't be negative");
+            }
+            return Ok(val as u64);
+        };
+
+        match words[0] {
+            "s" | "step" => {
+                let n = if words.len() > 1 { addr(1)? } else { 1 };
+                self.mode = Mode::Step(n.max(1));
+                return Ok(true);
+            },
+            "c" | "continue" => {
+                self.mode = Mode::Continue;
+                return Ok(true);
+            },
+            "o" | "next-output" => {
+                self.mode = Mode::NextOutput;
+                return Ok(true);
+            },
+            "detach" => {
+                self.mode = Mode::Detached;
+                return Ok(true);
+            },
+            "q" | "quit" => {
+                return Err(Quit.into());
+            },
+            "b" | "break" => {
+                self.breakpoints.insert(addr(1)?);
+            },
+            "d" | "delete" => {
+                let a = addr(1)?;
+                if !self.breakpoints.remove(&a) {
+                    println!("No breakpoint at {a}");
+                }
+            },
+            "w" | "watch" => {
+                let watch = match words.get(2).copied() {
+                    Some("r") => Watch::Read,
+                    Some("w") => Watch::Write,
+                    Some("rw") | None => Watch::ReadWrite,
+                    Some(w) => bail!("Invalid watch kind `{w}`, expected r, w or rw"),
+                };
+                self.watchpoints.insert(addr(1)?, watch);
+            },
+            "u" | "unwatch" => {
+                let a = addr(1)?;
+                if self.watchpoints.remove(&a).is_none() {
+                    println!("No watchpoint at {a}");
+                }
+            },
+            "i" | "info" => {
+                println!("ip = {}, rel_base = {}", computer.ip, computer.rel_base);
+                println!("arithmetic: {}", computer.arithmetic.name());
+                println!("history: {} instruction(s)", computer.history_len());
+                println!("breakpoints: {:?}", self.breakpoints);
+                println!("watchpoints: {:?}", self.watchpoints);
+            },
+            "x" => {
+                let start = addr(1)?;
+                let len = if words.len() > 2 { addr(2)? } else { 1 };
+                dump_memory(computer, start, len);
+            },
+            "rb" => {
+                let offset = if words.len() > 1 { arg(1)? } else { 0 };
+                let len = if words.len() > 2 { addr(2)? } else { 1 };
+                let start = computer.rel_base.checked_add(offset)
+                    .ok_or_else(|| anyhow!("rel_base{offset:+} doesn't fit in 64 bits"))?;
+                if start < 0 {
+                    bail!("rel_base{offset:+} is a negative address");
+                }
+                dump_memory(computer, start as u64, len);
+            },
+            "set" => {
+                let a = addr(1)?;
+                let val = arg(2)?;
+                computer.memory.write(a, val);
+            },
+            "arith" => {
+                if let Some(name) = words.get(1) {
+                    computer.arithmetic = Arithmetic::from_name(name)
+                        .ok_or_else(|| anyhow!("Unknown arithmetic `{name}`"))?;
+                }
+                println!("arithmetic: {}", computer.arithmetic.name());
+            },
+            "back" => {
+                let n = if words.len() > 1 { addr(1)? } else { 1 };
+                let undone = computer.rewind(n as usize);
+                println!("Went back {undone} instruction(s)");
+                print_position(computer);
+            },
+            "rewind" => {
+                let a = addr(1)?;
+                match computer.rewind_to_write(a) {
+                    Some(undone) => {
+                        println!("Went back {undone} instruction(s)");
+                        print_position(computer);
+                    },
+                    None => println!("No write to [{a}] in the history"),
+                }
+            },
+            "save" => {
+                let path = words.get(1)
+                    .ok_or_else(|| anyhow!("Missing snapshot path"))?;
+                computer.save(path)?;
+            },
+            "load" => {
+                let path = words.get(1)
+                    .ok_or_else(|| anyhow!("Missing snapshot path"))?;
+                *computer = Computer::load(path)?;
+                print_position(computer);
+            },
+            "mark" => {
+                self.mark = Some(computer.clone());
+            },
+            "diff" => {
+                let old = match words.get(1) {
+                    Some(path) => Computer::load(path)?,
+                    None => self.mark.clone()
+                        .ok_or_else(|| anyhow!("Nothing to compare with, use `mark` first"))?,
+                };
+                print!("{}", old.diff(computer));
+            },
+            "l" | "list" => {
+                let n = if words.len() > 1 { addr(1)? } else { 5 };
+                let mut a = computer.ip;
+                for _ in 0..n {
+                    let line = disasm::line_at(&computer.memory, a);
+                    println!("{line}");
+                    a += line.size();
+                }
+            },
+            "h" | "help" => {
+                println!("{HELP}");
+            },
+            cmd => {
+                bail!("Unknown command `{cmd}`, see `help`");
+            },
+        }
+
+        return Ok(false);
+    }
+}
+
+impl Observer for Debugger {
+    fn before_step(&mut self, computer: &mut Computer) -> std::result::Result<(), ComputerError> {
+        if self.mode == Mode::Detached {
+            return Ok(());
+        }
+
+        if let Some(reason) = self.stop_reason(computer) {
+            // Only VMs that actually stop pay for recording, from the first
+            // stop on, which is as far back as `back` can go.
+            computer.enable_history(HISTORY_LIMIT);
+            println!("Stopped: {reason}");
+            print_position(computer);
+            self.prompt(computer).map_err(|e| ComputerError::Stopped {
+                ip: computer.ip,
+                rel_base: computer.rel_base,
+                reason: e.to_string(),
+            })?;
+        }
+
+        return Ok(());
+    }
+
+    fn after_step(&mut self, computer: &Computer, event: &Event) {
+        match event.instruction.opcode {
+            Opcode::Output if self.mode == Mode::NextOutput => {
+                println!("Output: {}", event.instruction.operands()[0]);
+                self.mode = Mode::Step(1);
+            },
+            Opcode::Halt if matches!(self.mode, Mode::Step(_) | Mode::NextOutput) => {
+                println!("Halted at {}", computer.ip);
+            },
+            _ => {},
+        }
+    }
+}
+
+fn print_position(computer: &Computer) {
+    println!("rel_base = {}", computer.rel_base);
+    println!("{}", disasm::line_at(&computer.memory, computer.ip));
+}
+
+fn dump_memory(computer: &Computer, start: u64, len: u64) {
+    let end = start.saturating_add(len);
+    for row_start in (start..end).step_by(8) {
+        let row = (row_start..row_start.saturating_add(8).min(end))
+            .map(|a| format!("{:>8}", computer.memory.read_big(a)))
+            .collect::<Vec<_>>()
+            .join(" ");
+        println!("{row_start:>6}: {row}");
+    }
+}
+
+#[derive(Debug)]
+struct Quit;
+
+impl std::fmt::Display for Quit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return write!(f, "Debugging session aborted");
+    }
+}
+
+impl std::error::Error for Quit {}
+
+pub(crate) fn command(args: &[String]) -> Result<()> {
+    if args.len() < 2 {
+        bail!("Usage: debug <day> <input_file> [part]");
+    }
+
+    println!("Type `help` for the list of commands.");
+    attach_global_observer(Arc::new(Mutex::new(Debugger::new())));
+
+    return crate::run_day_with_args(args);
+}
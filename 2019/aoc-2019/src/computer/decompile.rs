@@ -0,0 +1,656 @@
+#![allow(clippy::needless_return)]
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+use anyhow::{Result, bail};
+
+use super::{Decoded, Memory, Opcode, Parameter, ParameterMode, cfg::{self, Cfg, Exit}};
+
+// Turns a program into structured pseudo-code, one function per call target
+// found by `cfg::build` plus `main` at address 0.
+//
+// Inside a function, `rel_base` cells are named by their offset from where
+// `rel_base` pointed on entry, which is where the caller put the return
+// address. The offsets after it up to the size of the frame (the `ARB` the
+// function starts with) are arguments if the function reads them before
+// writing them, and locals otherwise. Everything past the frame is `out<n>`,
+// the slots the function fills in when calling others.
+pub(crate) fn decompile(memory: &Memory) -> String {
+    let cfg = cfg::build(memory);
+
+    let mut entries = BTreeSet::from([0]);
+    for block in cfg.blocks.values() {
+        if let Exit::Call { target, .. } = block.exit {
+            entries.insert(target);
+        }
+    }
+
+    let code = entries.iter()
+        .filter(|entry| cfg.blocks.contains_key(entry))
+        .map(|&entry| Function::new(&cfg, entry).decompile())
+        .collect::<Vec<_>>();
+
+    return code.join("\n");
+}
+
+fn function_name(entry: u64) -> String {
+    if entry == 0 {
+        return String::from("main");
+    }
+    return format!("func_{entry}");
+}
+
+struct Loop {
+    body: BTreeSet<u64>,
+    // Where the loop exits to, other exits become gotos.
+    follow: Option<u64>,
+}
+
+enum Line {
+    // Start of a block, only printed if something jumps to it with a goto.
+    Label(usize, u64),
+    Code(usize, String),
+}
+
+struct Function<'a> {
+    cfg: &'a Cfg,
+    entry: u64,
+    blocks: BTreeSet<u64>,
+    // Offset of `rel_base` from its value on entry at the start of each
+    // block, `None` once it's been moved by a value from memory.
+    rb: HashMap<u64, Option<i64>>,
+    frame: i64,
+    names: HashMap<i64, String>,
+    idom: HashMap<u64, u64>,
+    ipdom: HashMap<u64, u64>,
+    loops: HashMap<u64, Loop>,
+    lines: Vec<Line>,
+    emitted: HashSet<u64>,
+    gotos: HashSet<u64>,
+}
+
+impl<'a> Function<'a> {
+    fn new(cfg: &'a Cfg, entry: u64) -> Self {
+        let mut blocks = BTreeSet::new();
+        let mut rb = HashMap::from([(entry, Some(0))]);
+        let mut pending = vec![entry];
+        while let Some(start) = pending.pop() {
+            if !blocks.insert(start) {
+                continue;
+            }
+
+            let block = &cfg.blocks[&start];
+            let mut offset = rb[&start];
+            for (_, decoded) in &block.instructions {
+                offset = adjust_rb(offset, decoded);
+            }
+            for next in block.exit.local_successors() {
+                rb.entry(next).or_insert(offset);
+                pending.push(next);
+            }
+        }
+
+        let frame = match cfg.blocks[&entry].instructions.first() {
+            Some((_, decoded)) if decoded.opcode == Opcode::AdjRelBase
+                    && decoded.params[0].mode == ParameterMode::Immediate => {
+                decoded.params[0].val.max(0)
+            },
+            _ => 0,
+        };
+
+        let mut function = Function {
+            cfg,
+            entry,
+            blocks,
+            rb,
+            frame,
+            names: HashMap::new(),
+            idom: HashMap::new(),
+            ipdom: HashMap::new(),
+            loops: HashMap::new(),
+            lines: Vec::new(),
+            emitted: HashSet::new(),
+            gotos: HashSet::new(),
+        };
+        function.name_cells();
+        function.find_dominators();
+        function.find_loops();
+
+        return function;
+    }
+
+    fn successors(&self, block: u64) -> Vec<u64> {
+        return self.cfg.blocks[&block].exit.local_successors();
+    }
+
+    fn predecessors(&self, block: u64) -> Vec<u64> {
+        return self.blocks.iter()
+            .copied()
+            .filter(|&b| self.successors(b).contains(&block))
+            .collect();
+    }
+
+    // Every instruction of the function in address order, with the offset
+    // of `rel_base` when it runs.
+    fn instructions(&self) -> Vec<(u64, Decoded, Option<i64>)> {
+        let mut instructions = Vec::new();
+        for start in &self.blocks {
+            let mut offset = self.rb[start];
+            for &(addr, decoded) in &self.cfg.blocks[start].instructions {
+                instructions.push((addr, decoded, offset));
+                offset = adjust_rb(offset, &decoded);
+            }
+        }
+        return instructions;
+    }
+
+    fn name_cells(&mut self) {
+        // Whether each cell is read before it's written.
+        let mut read_first = BTreeMap::new();
+        for (_, decoded, offset) in self.instructions() {
+            let Some(offset) = offset else {
+                continue;
+            };
+            for (idx, p) in decoded.params().iter().enumerate() {
+                if p.mode == ParameterMode::Relative && let Some(cell) = p.val.checked_add(offset) {
+                    let is_write = decoded.opcode.is_write_param(idx);
+                    read_first.entry(cell).or_insert(!is_write);
+                }
+            }
+        }
+
+        for (offset, read_first) in read_first {
+            let name = if self.entry == 0 && (self.frame == 0 || offset < self.frame) {
+                // `rel_base` starts out at 0, so these are plain addresses.
+                format!("mem[{offset}]")
+            } else if offset < 0 {
+                format!("caller[{offset}]")
+            } else if offset == 0 {
+                String::from("ret")
+            } else if self.frame > 0 && offset >= self.frame {
+                format!("out{}", offset - self.frame)
+            } else if read_first {
+                format!("arg{offset}")
+            } else {
+                format!("local{offset}")
+            };
+            self.names.insert(offset, name);
+        }
+    }
+
+    fn find_dominators(&mut self) {
+        let dom = dominators(&self.blocks, &[self.entry], |b| self.successors(b), |b| self.predecessors(b));
+        self.idom = immediate(&dom);
+
+        // Post-dominators are dominators of the reversed graph, starting
+        // from every block the function can end in.
+        let ends = self.blocks.iter()
+            .copied()
+            .filter(|&b| self.successors(b).is_empty())
+            .collect::<Vec<_>>();
+        let pdom = dominators(&self.blocks, &ends, |b| self.predecessors(b), |b| self.successors(b));
+        self.ipdom = immediate(&pdom);
+    }
+
+    fn dominates(&self, a: u64, mut b: u64) -> bool {
+        loop {
+            if a == b {
+                return true;
+            }
+            match self.idom.get(&b) {
+                Some(&up) => b = up,
+                None => return false,
+            }
+        }
+    }
+
+    // Natural loops, one per block that's the target of a back edge.
+    fn find_loops(&mut self) {
+        for &tail in &self.blocks {
+            for header in self.successors(tail) {
+                if !self.dominates(header, tail) {
+                    continue;
+                }
+
+                let body = &mut self.loops.entry(header)
+                    .or_insert_with(|| Loop { body: BTreeSet::from([header]), follow: None })
+                    .body;
+                let mut pending = vec![tail];
+                while let Some(b) = pending.pop() {
+                    if body.insert(b) {
+                        pending.extend(self.blocks.iter()
+                            .copied()
+                            .filter(|&p| self.cfg.blocks[&p].exit.local_successors().contains(&b)));
+                    }
+                }
+            }
+        }
+
+        for (&header, l) in self.loops.iter_mut() {
+            let exits = l.body.iter()
+                .flat_map(|&b| self.cfg.blocks[&b].exit.local_successors())
+                .filter(|b| !l.body.contains(b))
+                .collect::<BTreeSet<_>>();
+            // Prefer the exit of a condition checked at the top.
+            let from_header = self.cfg.blocks[&header].exit.local_successors()
+                .into_iter()
+                .find(|b| exits.contains(b));
+            l.follow = from_header.or_else(|| exits.first().copied());
+        }
+    }
+
+    fn decompile(mut self) -> String {
+        let args = self.names.iter()
+            .filter(|(_, name)| name.starts_with("arg"))
+            .collect::<BTreeMap<_, _>>()
+            .into_values()
+            .cloned()
+            .collect::<Vec<_>>();
+
+        let mut code = format!("fn {}({}) {{", function_name(self.entry), args.join(", "));
+        if self.frame > 0 {
+            code += &format!(" // @{}, frame of {}", self.entry, self.frame);
+        } else {
+            code += &format!(" // @{}", self.entry);
+        }
+        code += "\n";
+
+        self.emit_region(self.entry, None, &[], 1, false);
+
+        for line in &self.lines {
+            match line {
+                Line::Label(indent, addr) if self.gotos.contains(addr) => {
+                    code += &format!("{}L{addr}:\n", "    ".repeat(indent - 1));
+                },
+                Line::Label(..) => {},
+                Line::Code(indent, text) => {
+                    code += &format!("{}{text}\n", "    ".repeat(*indent));
+                },
+            }
+        }
+        code += "}\n";
+
+        return code;
+    }
+
+    fn push(&mut self, indent: usize, text: String) {
+        self.lines.push(Line::Code(indent, text));
+    }
+
+    fn goto(&mut self, indent: usize, block: u64) {
+        self.gotos.insert(block);
+        self.push(indent, format!("goto L{block}"));
+    }
+
+    // Emits the code from `start` on until control gets to `stop`. With
+    // `in_header` set, `start` is the header of the innermost loop, which is
+    // being emitted for the first time.
+    fn emit_region(
+        &mut self,
+        start: u64,
+        stop: Option<u64>,
+        enclosing: &[u64],
+        indent: usize,
+        mut in_header: bool,
+    ) {
+        let mut cur = Some(start);
+        while let Some(block) = cur {
+            if Some(block) == stop {
+                return;
+            }
+
+            if !std::mem::take(&mut in_header) {
+                if let Some((&innermost, outer)) = enclosing.split_last() {
+                    if block == innermost {
+                        self.push(indent, String::from("continue"));
+                        return;
+                    }
+                    if self.loops[&innermost].follow == Some(block) {
+                        self.push(indent, String::from("break"));
+                        return;
+                    }
+                    if outer.iter().any(|h| *h == block || self.loops[h].follow == Some(block)) {
+                        self.goto(indent, block);
+                        return;
+                    }
+                }
+                if self.emitted.contains(&block) {
+                    self.goto(indent, block);
+                    return;
+                }
+                if self.loops.contains_key(&block) {
+                    cur = self.emit_loop(block, enclosing, indent);
+                    continue;
+                }
+            }
+
+            self.emitted.insert(block);
+            self.lines.push(Line::Label(indent, block));
+            cur = self.emit_block(block, enclosing, indent);
+        }
+    }
+
+    fn emit_loop(&mut self, header: u64, enclosing: &[u64], indent: usize) -> Option<u64> {
+        let mut inner = enclosing.to_vec();
+        inner.push(header);
+
+        let cfg = self.cfg;
+        let block = &cfg.blocks[&header];
+        let follow = self.loops[&header].follow;
+        let start = self.lines.len();
+
+        // A header that only checks a condition makes a `while` loop.
+        if let [(_, jump)] = block.instructions[..]
+                && let Exit::Branch { target, next } = block.exit
+                && (follow == Some(target) || follow == Some(next)) {
+            let (stay, taken) = if follow == Some(next) { (target, true) } else { (next, false) };
+            let cond = self.condition(jump.opcode, jump.params[0], self.rb[&header], taken);
+
+            self.emitted.insert(header);
+            self.lines.push(Line::Label(indent, header));
+            self.push(indent, format!("while ({cond}) {{"));
+            self.emit_region(stay, None, &inner, indent + 1, false);
+        } else {
+            self.push(indent, String::from("loop {"));
+            self.emit_region(header, None, &inner, indent + 1, true);
+        }
+
+        if let Some(Line::Code(i, text)) = self.lines.last()
+                && *i == indent + 1
+                && text == "continue" {
+            self.lines.pop();
+        }
+
+        // A condition checked at the bottom makes a `do` loop.
+        let len = self.lines.len();
+        if let [.., Line::Code(_, opening), Line::Code(_, body), Line::Code(_, close), Line::Code(i, brk)] =
+                &self.lines[..]
+                && *i == indent + 1
+                && brk == "break"
+                && body == "continue"
+                && close == "}"
+                && let Some(cond) = opening.strip_prefix("if (").and_then(|c| c.strip_suffix(") {"))
+                && let Some(Line::Code(_, top)) = self.lines.get(start)
+                && top == "loop {" {
+            let cond = cond.to_string();
+            self.lines.truncate(len - 4);
+            self.lines[start] = Line::Code(indent, String::from("do {"));
+            self.push(indent, format!("}} while ({cond})"));
+        } else {
+            self.push(indent, String::from("}"));
+        }
+
+        return follow;
+    }
+
+    // Emits one block and returns where control goes next.
+    fn emit_block(&mut self, start: u64, enclosing: &[u64], indent: usize) -> Option<u64> {
+        let cfg = self.cfg;
+        let block = &cfg.blocks[&start];
+
+        // The store of the return address is part of the call.
+        let ret_store = match block.exit {
+            Exit::Call { ret, .. } => block.instructions.iter()
+                .rposition(|(_, decoded)| cfg::relative_store(decoded) == Some(ret as i64)),
+            _ => None,
+        };
+
+        let mut offset = self.rb[&start];
+        let mut ret_offset = None;
+        let mut written = HashSet::new();
+        for (idx, (_, decoded)) in block.instructions.iter().enumerate() {
+            let dst = decoded.params[2];
+            if Some(idx) == ret_store {
+                ret_offset = offset.and_then(|o| dst.val.checked_add(o));
+            } else {
+                if let Some(text) = self.statement(decoded, offset) {
+                    self.push(indent, text);
+                }
+                for (p_idx, p) in decoded.params().iter().enumerate() {
+                    if decoded.opcode.is_write_param(p_idx) && p.mode == ParameterMode::Relative
+                            && let Some(cell) = offset.and_then(|o| p.val.checked_add(o)) {
+                        written.insert(cell);
+                    }
+                }
+            }
+            offset = adjust_rb(offset, decoded);
+        }
+
+        return match block.exit {
+            Exit::Next(next) | Exit::Jump(next) => Some(next),
+            Exit::Call { target, ret } => {
+                let args = match ret_offset {
+                    Some(ret_offset) => (1..)
+                        .map_while(|i| ret_offset.checked_add(i))
+                        .take_while(|o| written.contains(o))
+                        .map(|o| self.cell_name(o))
+                        .collect::<Vec<_>>(),
+                    None => vec![],
+                };
+                self.push(indent, format!("{}({})", function_name(target), args.join(", ")));
+                Some(ret)
+            },
+            Exit::Branch { target, next } => {
+                self.emit_if(start, (target, next), offset, enclosing, indent)
+            },
+            Exit::Indirect { next } => {
+                // Only blocks ending in a jump are supposed to get here, but
+                // others can be empty, e.g. with an invalid opcode at the start.
+                let Some((_, jump)) = block.instructions.last() else {
+                    self.push(indent, format!("unknown() // @{}", block.end()));
+                    return None;
+                };
+                let [cond, target, _] = jump.params;
+                let target = self.operand(target, offset);
+                match next {
+                    Some(_) => {
+                        let cond = self.condition(jump.opcode, cond, offset, true);
+                        self.push(indent, format!("if ({cond}) {{"));
+                        self.push(indent + 1, format!("goto *{target}"));
+                        self.push(indent, String::from("}"));
+                    },
+                    None => self.push(indent, format!("goto *{target}")),
+                }
+                next
+            },
+            Exit::Return => {
+                self.push(indent, String::from("return"));
+                None
+            },
+            Exit::Halt => {
+                self.push(indent, String::from("halt()"));
+                None
+            },
+            Exit::Invalid => {
+                self.push(indent, format!("invalid() // @{}", block.end()));
+                None
+            },
+        };
+    }
+
+    // Emits the branch at the end of `block` to `target` or `next`.
+    fn emit_if(
+        &mut self,
+        block: u64,
+        (target, next): (u64, u64),
+        offset: Option<i64>,
+        enclosing: &[u64],
+        indent: usize,
+    ) -> Option<u64> {
+        let cfg = self.cfg;
+        let (_, jump) = cfg.blocks[&block].instructions.last().unwrap();
+
+        // Both sides meet again at the post-dominator, unless that's outside
+        // the loop being emitted.
+        let join = self.ipdom.get(&block).copied().filter(|join| {
+            return match enclosing.last() {
+                Some(header) => {
+                    let l = &self.loops[header];
+                    l.body.contains(join) || l.follow == Some(*join)
+                },
+                None => true,
+            };
+        });
+
+        let (taken, then, other) = match (Some(target) == join, Some(next) == join) {
+            (true, _) => (false, next, target),
+            _ => (true, target, next),
+        };
+        let cond = self.condition(jump.opcode, jump.params[0], offset, taken);
+
+        self.push(indent, format!("if ({cond}) {{"));
+        self.emit_region(then, join, enclosing, indent + 1, false);
+        if Some(other) != join {
+            self.push(indent, String::from("} else {"));
+            self.emit_region(other, join, enclosing, indent + 1, false);
+        }
+        self.push(indent, String::from("}"));
+
+        return join;
+    }
+
+    // The condition under which a jump is taken, or not taken.
+    fn condition(&self, opcode: Opcode, cond: Parameter, offset: Option<i64>, taken: bool) -> String {
+        let nonzero = (opcode == Opcode::JumpIfTrue) == taken;
+        let cond = self.operand(cond, offset);
+        return if nonzero { format!("{cond} != 0") } else { format!("{cond} == 0") };
+    }
+
+    fn cell_name(&self, offset: i64) -> String {
+        return self.names.get(&offset)
+            .cloned()
+            .unwrap_or_else(|| format!("frame[{offset}]"));
+    }
+
+    fn operand(&self, p: Parameter, offset: Option<i64>) -> String {
+        // Cells past the end of the address space are left as they're written.
+        return match (p.mode, offset.and_then(|o| p.val.checked_add(o))) {
+            (ParameterMode::Immediate, _) => p.val.to_string(),
+            (ParameterMode::Position, _) => format!("mem[{}]", p.val),
+            (ParameterMode::Relative, Some(cell)) => self.cell_name(cell),
+            (ParameterMode::Relative, None) => format!("rb[{}]", p.val),
+        };
+    }
+
+    fn statement(&self, decoded: &Decoded, offset: Option<i64>) -> Option<String> {
+        let [a, b, c] = decoded.params;
+        let x = |p: Parameter| self.operand(p, offset);
+        let imm = |p: Parameter, val: i64| p.mode == ParameterMode::Immediate && p.val == val;
+
+        let value = match decoded.opcode {
+            Opcode::Add if imm(a, 0) => x(b),
+            Opcode::Add if imm(b, 0) => x(a),
+            Opcode::Add if b.mode == ParameterMode::Immediate && b.val < 0 => {
+                format!("{} - {}", x(a), b.val.unsigned_abs())
+            },
+            Opcode::Add => format!("{} + {}", x(a), x(b)),
+            Opcode::Mul if imm(a, 0) || imm(b, 0) => String::from("0"),
+            Opcode::Mul if imm(a, 1) => x(b),
+            Opcode::Mul if imm(b, 1) => x(a),
+            Opcode::Mul if imm(a, -1) => format!("-{}", x(b)),
+            Opcode::Mul if imm(b, -1) => format!("-{}", x(a)),
+            Opcode::Mul => format!("{} * {}", x(a), x(b)),
+            Opcode::LessThan => format!("{} < {}", x(a), x(b)),
+            Opcode::Equals => format!("{} == {}", x(a), x(b)),
+            Opcode::Input => return Some(format!("{} = input()", x(a))),
+            Opcode::Output => return Some(format!("output({})", x(a))),
+            // Immediate adjustments are accounted for in the names.
+            Opcode::AdjRelBase if a.mode == ParameterMode::Immediate => return None,
+            Opcode::AdjRelBase => return Some(format!("rb += {}", x(a))),
+            Opcode::JumpIfTrue | Opcode::JumpIfFalse | Opcode::Halt => return None,
+            Opcode::Custom(_) => {
+                let args = decoded.params().iter()
+                    .map(|p| x(*p))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                return Some(format!("{}({args})", decoded.opcode.mnemonic().to_lowercase()));
+            },
+        };
+
+        return Some(format!("{} = {value}", x(c)));
+    }
+}
+
+fn adjust_rb(offset: Option<i64>, decoded: &Decoded) -> Option<i64> {
+    if decoded.opcode != Opcode::AdjRelBase {
+        return offset;
+    }
+    let p = decoded.params[0];
+    return match p.mode {
+        ParameterMode::Immediate => offset.and_then(|o| o.checked_add(p.val)),
+        _ => None,
+    };
+}
+
+// Dominator sets of every node reachable from `roots`, by iterating until
+// nothing changes.
+fn dominators(
+    nodes: &BTreeSet<u64>,
+    roots: &[u64],
+    next: impl Fn(u64) -> Vec<u64>,
+    prev: impl Fn(u64) -> Vec<u64>,
+) -> HashMap<u64, BTreeSet<u64>> {
+    let mut reached = BTreeSet::new();
+    let mut pending = roots.to_vec();
+    while let Some(node) = pending.pop() {
+        if nodes.contains(&node) && reached.insert(node) {
+            pending.extend(next(node));
+        }
+    }
+
+    let mut dom = reached.iter()
+        .map(|&n| (n, if roots.contains(&n) { BTreeSet::from([n]) } else { reached.clone() }))
+        .collect::<HashMap<_, _>>();
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for &node in &reached {
+            if roots.contains(&node) {
+                continue;
+            }
+
+            let mut new: Option<BTreeSet<u64>> = None;
+            for p in prev(node).into_iter().filter(|p| reached.contains(p)) {
+                new = Some(match new {
+                    Some(set) => set.intersection(&dom[&p]).copied().collect(),
+                    None => dom[&p].clone(),
+                });
+            }
+            let mut new = new.unwrap_or_default();
+            new.insert(node);
+
+            if new != dom[&node] {
+                dom.insert(node, new);
+                changed = true;
+            }
+        }
+    }
+
+    return dom;
+}
+
+// The closest strict dominator of each node, which is the one with the most
+// dominators itself.
+fn immediate(dom: &HashMap<u64, BTreeSet<u64>>) -> HashMap<u64, u64> {
+    let mut idom = HashMap::new();
+    for (&node, doms) in dom {
+        let closest = doms.iter()
+            .filter(|&&d| d != node)
+            .max_by_key(|d| dom[d].len());
+        if let Some(&closest) = closest {
+            idom.insert(node, closest);
+        }
+    }
+    return idom;
+}
+
+pub(crate) fn command(args: &[String]) -> Result<()> {
+    if args.is_empty() {
+        bail!("Usage: decompile <input_file>");
+    }
+
+    let computer = super::asm::load(&args[0])?;
+    print!("{}", decompile(&computer.memory));
+
+    return Ok(());
+}
@@ -0,0 +1,137 @@
+#![allow(clippy::needless_return)]
+
+use std::{collections::BTreeSet, fmt::Display, fs};
+
+use anyhow::{Context, Result, bail};
+
+use super::{Computer, bigint::BigInt};
+
+// Everything that differs between two VMs. Changed cells are grouped into
+// runs of consecutive addresses.
+#[derive(Debug, Default)]
+pub(crate) struct Diff {
+    pub(crate) ip: Option<(u64, u64)>,
+    pub(crate) rel_base: Option<(i64, i64)>,
+    pub(crate) ranges: Vec<ChangedRange>,
+    // Cells where either side holds a value that doesn't fit in an i64.
+    pub(crate) big: Vec<(u64, BigInt, BigInt)>,
+}
+
+#[derive(Debug)]
+pub(crate) struct ChangedRange {
+    pub(crate) start: u64,
+    pub(crate) old: Vec<i64>,
+    pub(crate) new: Vec<i64>,
+}
+
+impl Diff {
+    pub(crate) fn is_empty(&self) -> bool {
+        return self.ip.is_none()
+            && self.rel_base.is_none()
+            && self.ranges.is_empty()
+            && self.big.is_empty();
+    }
+}
+
+impl Computer {
+    // What changed going from `self` to `other`.
+    pub(crate) fn diff(&self, other: &Computer) -> Diff {
+        let mut diff = Diff::default();
+        if self.ip != other.ip {
+            diff.ip = Some((self.ip, other.ip));
+        }
+        if self.rel_base != other.rel_base {
+            diff.rel_base = Some((self.rel_base, other.rel_base));
+        }
+
+        // Cells missing from both are zero on both sides.
+        let addrs = self.memory.cells().into_iter()
+            .chain(other.memory.cells())
+            .map(|(addr, _)| addr)
+            .collect::<BTreeSet<_>>();
+        for addr in addrs {
+            let (old, new) = (self.memory.read(addr), other.memory.read(addr));
+            if old == new {
+                continue;
+            }
+
+            match diff.ranges.last_mut() {
+                Some(range) if range.start + range.old.len() as u64 == addr => {
+                    range.old.push(old);
+                    range.new.push(new);
+                },
+                _ => diff.ranges.push(ChangedRange { start: addr, old: vec![old], new: vec![new] }),
+            }
+        }
+
+        let big = self.memory.big_cells().into_iter()
+            .chain(other.memory.big_cells())
+            .map(|(addr, _)| addr)
+            .collect::<BTreeSet<_>>();
+        for addr in big {
+            let (old, new) = (self.memory.read_big(addr), other.memory.read_big(addr));
+            if old != new {
+                diff.big.push((addr, old, new));
+            }
+        }
+
+        return diff;
+    }
+}
+
+impl Display for Diff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "No differences");
+        }
+
+        if let Some((old, new)) = self.ip {
+            writeln!(f, "ip: {old} -> {new}")?;
+        }
+        if let Some((old, new)) = self.rel_base {
+            writeln!(f, "rel_base: {old} -> {new}")?;
+        }
+        for range in &self.ranges {
+            let end = range.start + range.old.len() as u64 - 1;
+            let addrs = match range.start == end {
+                true => format!("[{}]", range.start),
+                false => format!("[{}..={end}]", range.start),
+            };
+            writeln!(f, "{addrs}: {} -> {}", join(&range.old), join(&range.new))?;
+        }
+        for (addr, old, new) in &self.big {
+            writeln!(f, "[{addr}] (big): {old} -> {new}")?;
+        }
+
+        return Ok(());
+    }
+}
+
+fn join(values: &[i64]) -> String {
+    return values.iter()
+        .map(|v| v.to_string())
+        .collect::<Vec<_>>()
+        .join(",");
+}
+
+// A snapshot, or a program that hasn't started yet.
+fn load_state(path: &str) -> Result<Computer> {
+    let contents = fs::read_to_string(path)
+        .with_context(|| format!("Couldn't read `{path}`"))?;
+    if contents.starts_with(super::snapshot::HEADER) {
+        return Computer::load(path);
+    }
+    return super::asm::load(path);
+}
+
+pub(crate) fn command(args: &[String]) -> Result<()> {
+    if args.len() < 2 {
+        bail!("Usage: diff <old_file> <new_file>");
+    }
+
+    let old = load_state(&args[0])?;
+    let new = load_state(&args[1])?;
+    print!("{}", old.diff(&new));
+
+    return Ok(());
+}
@@ -0,0 +1,137 @@
+#![allow(clippy::needless_return)]
+
+use std::fmt::Display;
+
+use anyhow::{Result, bail};
+
+use super::{Decoded, Memory, Parameter, ParameterMode, decode};
+
+pub(crate) enum Line {
+    Instruction {
+        addr: u64,
+        decoded: Decoded,
+        words: Vec<i64>,
+    },
+    Data {
+        addr: u64,
+        val: i64,
+    },
+}
+
+pub(crate) fn disassemble(memory: &Memory) -> Vec<Line> {
+    let len = memory.len();
+    let mut lines = Vec::new();
+
+    let mut addr = 0;
+    while addr < len {
+        let line = disassemble_line(memory, addr, len);
+        addr += line.size();
+        lines.push(line);
+    }
+
+    return lines;
+}
+
+// Single line at `addr`, for looking at code while it's running.
+pub(crate) fn line_at(memory: &Memory, addr: u64) -> Line {
+    return disassemble_line(memory, addr, u64::MAX);
+}
+
+// Line at `addr` of a program `len` cells long.
+pub(crate) fn disassemble_line(memory: &Memory, addr: u64, len: u64) -> Line {
+    return match decode_at(memory, addr, len) {
+        Some(decoded) => {
+            let words = (addr..addr + decoded.size())
+                .map(|a| memory.read(a))
+                .collect();
+            Line::Instruction { addr, decoded, words }
+        },
+        None => Line::Data { addr, val: memory.read(addr) },
+    };
+}
+
+impl Line {
+    pub(crate) fn size(&self) -> u64 {
+        return match self {
+            Line::Instruction { decoded, .. } => decoded.size(),
+            Line::Data { .. } => 1,
+        };
+    }
+}
+
+// Only accepts instructions that fit inside the program and that could be
+// executed, everything else is listed as raw data.
+fn decode_at(memory: &Memory, addr: u64, len: u64) -> Option<Decoded> {
+    let decoded = decode(memory, addr).ok()?;
+    if addr.saturating_add(decoded.size()) > len {
+        return None;
+    }
+
+    let writes_immediate = decoded.params().iter()
+        .enumerate()
+        .any(|(idx, p)| {
+            decoded.opcode.is_write_param(idx) && p.mode == ParameterMode::Immediate
+        });
+    if writes_immediate {
+        return None;
+    }
+
+    return Some(decoded);
+}
+
+impl Display for Parameter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return match self.mode {
+            ParameterMode::Position => write!(f, "[{}]", self.val),
+            ParameterMode::Immediate => write!(f, "#{}", self.val),
+            ParameterMode::Relative if self.val < 0 => write!(f, "rb{}", self.val),
+            ParameterMode::Relative => write!(f, "rb+{}", self.val),
+        };
+    }
+}
+
+impl Line {
+    // Instruction text without the address and the raw words.
+    pub(crate) fn text(&self) -> String {
+        return match self {
+            Line::Instruction { decoded, .. } => {
+                let params = decoded.params().iter()
+                    .map(|p| p.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                format!("{} {params}", decoded.opcode.mnemonic())
+                    .trim_end()
+                    .to_string()
+            },
+            Line::Data { val, .. } => format!(".data {val}"),
+        };
+    }
+}
+
+impl Display for Line {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (addr, words) = match self {
+            Line::Instruction { addr, words, .. } => (addr, words.as_slice()),
+            Line::Data { addr, val } => (addr, std::slice::from_ref(val)),
+        };
+        let words = words.iter()
+            .map(|w| w.to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+
+        return write!(f, "{addr:>6}: {:<32} ; {words}", self.text());
+    }
+}
+
+pub(crate) fn command(args: &[String]) -> Result<()> {
+    if args.is_empty() {
+        bail!("Usage: disasm <input_file>");
+    }
+
+    let computer = super::asm::load(&args[0])?;
+    for line in disassemble(&computer.memory) {
+        println!("{line}");
+    }
+
+    return Ok(());
+}
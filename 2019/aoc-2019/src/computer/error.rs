@@ -0,0 +1,127 @@
+#![allow(clippy::needless_return)]
+
+use std::fmt::Display;
+
+use super::Opcode;
+
+// Faults raised while running an Intcode program. `ip` is always the address
+// of the instruction that faulted, `param` the 1-based parameter index.
+// Decoding faults carry the instruction word, which is all decoding looks at,
+// everything else also carries `rel_base` and what was being run.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) enum ComputerError {
+    InvalidOpcode {
+        ip: u64,
+        word: i64,
+    },
+    InvalidMode {
+        ip: u64,
+        word: i64,
+        param: usize,
+    },
+    NegativeAddress {
+        ip: u64,
+        rel_base: i64,
+        opcode: Opcode,
+        addr: i64,
+    },
+    // A relative-mode address that doesn't fit in 64 bits.
+    AddressOverflow {
+        ip: u64,
+        rel_base: i64,
+        opcode: Opcode,
+        offset: i64,
+    },
+    WriteToImmediate {
+        ip: u64,
+        rel_base: i64,
+        opcode: Opcode,
+        word: i64,
+        param: usize,
+    },
+    // Always on an `IN` instruction.
+    InputExhausted {
+        ip: u64,
+        rel_base: i64,
+    },
+    Livelocked {
+        ip: u64,
+        rel_base: i64,
+    },
+    Overflow {
+        ip: u64,
+        rel_base: i64,
+        opcode: Opcode,
+        a: i64,
+        b: i64,
+    },
+    // A big-integer value was used where only an i64 makes sense, e.g. as
+    // an output or a jump target.
+    ValueOutOfRange {
+        ip: u64,
+        rel_base: i64,
+        opcode: Opcode,
+        param: usize,
+    },
+    // A registered opcode ended the program with a non-zero exit code.
+    Exited {
+        ip: u64,
+        rel_base: i64,
+        opcode: Opcode,
+        code: i64,
+    },
+    // An observer, e.g. the debugger, stopped the program.
+    Stopped {
+        ip: u64,
+        rel_base: i64,
+        reason: String,
+    },
+}
+
+impl Display for ComputerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return match self {
+            ComputerError::InvalidOpcode { ip, word } => {
+                write!(f, "Invalid op code {} in `{word}` at ip {ip}", word % 100)
+            },
+            ComputerError::InvalidMode { ip, word, param } => {
+                write!(f, "Invalid mode for parameter {param} in `{word}` at ip {ip}")
+            },
+            ComputerError::NegativeAddress { ip, rel_base, opcode, addr } => {
+                write!(f, "Negative address {addr} in `{}` at ip {ip} (rel_base = {rel_base})",
+                    opcode.mnemonic())
+            },
+            ComputerError::AddressOverflow { ip, rel_base, opcode, offset } => {
+                write!(f, "Relative address {offset} overflowed in `{}` at ip {ip} \
+                    (rel_base = {rel_base})", opcode.mnemonic())
+            },
+            ComputerError::WriteToImmediate { ip, rel_base, opcode, word, param } => {
+                write!(f, "Parameter {param} of `{}` in `{word}` at ip {ip} is written to \
+                    but uses immediate mode (rel_base = {rel_base})", opcode.mnemonic())
+            },
+            ComputerError::InputExhausted { ip, rel_base } => {
+                write!(f, "Program needs more input at ip {ip} (rel_base = {rel_base})")
+            },
+            ComputerError::Livelocked { ip, rel_base } => {
+                write!(f, "Program is stuck in an endless loop at ip {ip} (rel_base = {rel_base})")
+            },
+            ComputerError::Overflow { ip, rel_base, opcode, a, b } => {
+                write!(f, "{} of {a} and {b} overflowed at ip {ip} (rel_base = {rel_base})",
+                    opcode.mnemonic())
+            },
+            ComputerError::ValueOutOfRange { ip, rel_base, opcode, param } => {
+                write!(f, "Parameter {param} of `{}` at ip {ip} doesn't fit in 64 bits \
+                    (rel_base = {rel_base})", opcode.mnemonic())
+            },
+            ComputerError::Exited { ip, rel_base, opcode, code } => {
+                write!(f, "Program exited with code {code} from `{}` at ip {ip} \
+                    (rel_base = {rel_base})", opcode.mnemonic())
+            },
+            ComputerError::Stopped { ip, rel_base, reason } => {
+                write!(f, "Program stopped at ip {ip} (rel_base = {rel_base}): {reason}")
+            },
+        };
+    }
+}
+
+impl std::error::Error for ComputerError {}
@@ -0,0 +1,360 @@
+#![allow(clippy::needless_return)]
+
+use std::{collections::HashSet, fs, path::Path, sync::{Arc, Mutex}};
+
+use anyhow::{Context, Result, anyhow, bail};
+
+use super::{Computer, ComputerError, Observer, Status, io::IterIo};
+
+// Instructions a single run may execute before it counts as a hang.
+const DEFAULT_BUDGET: u64 = 1_000_000;
+const DEFAULT_RUNS: u64 = 10_000;
+const DEFAULT_MAX_LEN: usize = 64;
+
+// Values that tend to hit edge cases, next to random ones.
+const INTERESTING: [i64; 10] = [0, 1, -1, 2, 10, 99, 1000, i64::MAX, i64::MIN, -1000];
+
+// SplitMix64, good enough to pick mutations and reproducible from a seed.
+pub(crate) struct Rng(u64);
+
+impl Rng {
+    pub(crate) fn new(seed: u64) -> Self {
+        return Rng(seed);
+    }
+
+    pub(crate) fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        return z ^ (z >> 31);
+    }
+
+    // Uniform enough in `0..n` for the small `n` used here, 0 for an empty
+    // range.
+    pub(crate) fn below(&mut self, n: usize) -> usize {
+        return self.next().checked_rem(n as u64).unwrap_or(0) as usize;
+    }
+}
+
+// Addresses of the instructions executed by the current run.
+#[derive(Default)]
+struct Hits(HashSet<u64>);
+
+impl Observer for Hits {
+    fn before_step(&mut self, computer: &mut Computer) -> std::result::Result<(), ComputerError> {
+        self.0.insert(computer.ip);
+        return Ok(());
+    }
+}
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+enum Kind {
+    Crash,
+    Hang,
+    Halt,
+}
+
+impl Kind {
+    fn name(&self) -> &'static str {
+        return match self {
+            Kind::Crash => "crash",
+            Kind::Hang => "hang",
+            Kind::Halt => "halt",
+        };
+    }
+}
+
+// What a single run ended with.
+enum Outcome {
+    Status(Status),
+    Error(ComputerError),
+}
+
+impl Outcome {
+    fn describe(&self, ip: u64) -> String {
+        return match self {
+            Outcome::Status(status) => format!("{status:?} at {ip}"),
+            Outcome::Error(e) => e.to_string(),
+        };
+    }
+}
+
+pub(crate) struct FuzzOptions {
+    pub(crate) seed: u64,
+    pub(crate) runs: u64,
+    pub(crate) budget: u64,
+    pub(crate) max_len: usize,
+    // Whether halting counts as a finding, for programs that should keep
+    // asking for input.
+    pub(crate) no_halt: bool,
+}
+
+impl Default for FuzzOptions {
+    fn default() -> Self {
+        return FuzzOptions {
+            seed: 1,
+            runs: DEFAULT_RUNS,
+            budget: DEFAULT_BUDGET,
+            max_len: DEFAULT_MAX_LEN,
+            no_halt: false,
+        };
+    }
+}
+
+// Feeds a program mutated input sequences and keeps the ones that execute
+// an instruction no earlier run did, so later mutations build on them.
+// Runs that fail, hang or halt when they shouldn't are saved to `out_dir`,
+// one per kind and address.
+pub(crate) struct Fuzzer {
+    computer: Computer,
+    hits: Arc<Mutex<Hits>>,
+    options: FuzzOptions,
+    rng: Rng,
+    corpus: Vec<Vec<i64>>,
+    covered: HashSet<u64>,
+    // Kind and instruction address of every saved finding.
+    found: HashSet<(Kind, u64)>,
+    out_dir: String,
+}
+
+impl Fuzzer {
+    pub(crate) fn new(mut computer: Computer, options: FuzzOptions, out_dir: &str) -> Self {
+        let hits = Arc::new(Mutex::new(Hits::default()));
+        computer.attach_observer(hits.clone());
+        computer.enable_livelock_detection();
+
+        return Fuzzer {
+            computer,
+            hits,
+            rng: Rng::new(options.seed),
+            options,
+            corpus: Vec::new(),
+            covered: HashSet::new(),
+            found: HashSet::new(),
+            out_dir: out_dir.to_string(),
+        };
+    }
+
+    pub(crate) fn run(&mut self) -> Result<()> {
+        fs::create_dir_all(&self.out_dir)
+            .with_context(|| format!("Couldn't create `{}`", self.out_dir))?;
+
+        // Mutations start from the corpus, so it has the empty input even when
+        // that covered nothing, e.g. with a budget of 0.
+        self.try_input(Vec::new())?;
+        if self.corpus.is_empty() {
+            self.corpus.push(Vec::new());
+        }
+        for _ in 0..self.options.runs {
+            let input = self.mutate();
+            self.try_input(input)?;
+        }
+
+        println!(
+            "{} run(s), {} input(s) in the corpus, {} address(es) covered, {} finding(s)",
+            self.options.runs + 1,
+            self.corpus.len(),
+            self.covered.len(),
+            self.found.len(),
+        );
+        return Ok(());
+    }
+
+    fn try_input(&mut self, input: Vec<i64>) -> Result<()> {
+        self.hits.lock().unwrap().0.clear();
+        let mut computer = self.computer.clone();
+        let outcome = run(&mut computer, &input, self.options.budget);
+
+        let new = self.hits.lock().unwrap().0.iter()
+            .filter(|addr| self.covered.insert(**addr))
+            .count();
+        if new > 0 {
+            self.corpus.push(input.clone());
+        }
+
+        let kind = match &outcome {
+            Outcome::Error(_) => Kind::Crash,
+            Outcome::Status(Status::BudgetExhausted | Status::Livelocked) => Kind::Hang,
+            Outcome::Status(Status::Halted) if self.options.no_halt => Kind::Halt,
+            Outcome::Status(_) => return Ok(()),
+        };
+        if self.found.insert((kind.clone(), computer.ip)) {
+            self.save(&kind, &input, &outcome, computer.ip)?;
+        }
+
+        return Ok(());
+    }
+
+    fn save(&self, kind: &Kind, input: &[i64], outcome: &Outcome, ip: u64) -> Result<()> {
+        let path = Path::new(&self.out_dir).join(format!("{}-{}.txt", kind.name(), self.found.len()));
+        let contents = format!("{}\n# {}\n", join(input), outcome.describe(ip));
+        fs::write(&path, contents)
+            .with_context(|| format!("Couldn't write `{}`", path.display()))?;
+
+        println!("{}: {}", path.display(), outcome.describe(ip));
+        return Ok(());
+    }
+
+    fn mutate(&mut self) -> Vec<i64> {
+        let mut input = self.corpus[self.rng.below(self.corpus.len())].clone();
+
+        for _ in 0..=self.rng.below(4) {
+            let len = input.len();
+            match self.rng.below(7) {
+                0 => {
+                    let val = self.value();
+                    input.push(val);
+                },
+                1 if len > 0 => {
+                    let idx = self.rng.below(len);
+                    input[idx] = self.value();
+                },
+                2 => {
+                    let idx = self.rng.below(len + 1);
+                    let val = self.value();
+                    input.insert(idx, val);
+                },
+                3 if len > 0 => {
+                    input.remove(self.rng.below(len));
+                },
+                4 if len > 0 => {
+                    let idx = self.rng.below(len);
+                    let delta = self.rng.below(17) as i64 - 8;
+                    input[idx] = input[idx].wrapping_add(delta);
+                },
+                5 => {
+                    // Tail of another input, e.g. to get past a prompt again.
+                    let other = &self.corpus[self.rng.below(self.corpus.len())];
+                    let from = self.rng.below(other.len() + 1);
+                    input.extend_from_slice(&other[from..]);
+                },
+                _ => {
+                    // A line of text for ASCII programs.
+                    for _ in 0..self.rng.below(8) {
+                        input.push(b'a' as i64 + self.rng.below(26) as i64);
+                    }
+                    input.push(10);
+                },
+            }
+        }
+
+        input.truncate(self.options.max_len);
+        return input;
+    }
+
+    fn value(&mut self) -> i64 {
+        return match self.rng.below(4) {
+            0 => INTERESTING[self.rng.below(INTERESTING.len())],
+            1 => self.rng.below(201) as i64 - 100,
+            2 => self.rng.below(128) as i64,
+            _ => self.rng.next() as i64,
+        };
+    }
+}
+
+fn run(computer: &mut Computer, input: &[i64], budget: u64) -> Outcome {
+    return match computer.run_with_budget(input.iter().copied(), budget) {
+        Ok(output) => Outcome::Status(output.status),
+        Err(e) => Outcome::Error(e),
+    };
+}
+
+fn join(values: &[i64]) -> String {
+    return values.iter()
+        .map(|v| v.to_string())
+        .collect::<Vec<_>>()
+        .join(",");
+}
+
+// Runs a saved finding again and shows what happens.
+fn replay(mut computer: Computer, case_file: &str, budget: u64) -> Result<()> {
+    let contents = fs::read_to_string(case_file)
+        .with_context(|| format!("Couldn't read `{case_file}`"))?;
+    let line = contents.lines().next().unwrap_or("");
+    let input = line.split(",")
+        .filter(|v| !v.is_empty())
+        .map(|v| v.trim().parse::<i64>())
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .with_context(|| format!("Invalid input in `{case_file}`"))?;
+
+    computer.enable_livelock_detection();
+    let mut io = IterIo::new(input.into_iter());
+    let outcome = match computer.run_io_with_budget(&mut io, budget) {
+        Ok(status) => Outcome::Status(status),
+        Err(e) => Outcome::Error(e),
+    };
+    println!("outputs: {}", join(&io.outputs));
+    println!("{}", outcome.describe(computer.ip));
+
+    return Ok(());
+}
+
+pub(crate) fn command(args: &[String]) -> Result<()> {
+    let usage = "Usage: fuzz <input_file> <out_dir> [--seed n] [--runs n] [--budget n] \
+        [--max-len n] [--no-halt]\n       fuzz <input_file> --replay <case_file> [--budget n]";
+
+    let mut positional = Vec::new();
+    let mut options = FuzzOptions::default();
+    let mut replay_file = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| anyhow!("Missing value for `{arg}`"));
+        match arg.as_str() {
+            "--seed" => options.seed = value()?.parse().context("Invalid seed")?,
+            "--runs" => options.runs = value()?.parse().context("Invalid number of runs")?,
+            "--budget" => options.budget = value()?.parse().context("Invalid budget")?,
+            "--max-len" => options.max_len = value()?.parse().context("Invalid length")?,
+            "--no-halt" => options.no_halt = true,
+            "--replay" => replay_file = Some(value()?.clone()),
+            _ => positional.push(arg.clone()),
+        }
+    }
+
+    if positional.is_empty() {
+        bail!(usage);
+    }
+    let computer = super::asm::load(&positional[0])?;
+
+    if let Some(case_file) = replay_file {
+        return replay(computer, &case_file, options.budget);
+    }
+    if positional.len() < 2 {
+        bail!(usage);
+    }
+
+    return Fuzzer::new(computer, options, &positional[1]).run();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::computer::asm::assemble_computer;
+
+    #[test]
+    fn overflowing_inputs_are_crashes() {
+        let computer = assemble_computer("
+            loop:   IN [x]
+                    ARB [x]
+                    OUT rb+1
+                    JT #1, #loop
+            x:      .data 0
+        ").unwrap();
+        let out_dir = std::env::temp_dir().join(format!("aoc-2019-fuzz-{}", std::process::id()));
+        let out_dir = out_dir.to_str().unwrap();
+
+        let options = FuzzOptions { runs: 500, ..FuzzOptions::default() };
+        let mut fuzzer = Fuzzer::new(computer, options, out_dir);
+        let res = fuzzer.run();
+
+        let crashes = fs::read_dir(out_dir).unwrap()
+            .map(|entry| fs::read_to_string(entry.unwrap().path()).unwrap())
+            .filter(|contents| contents.contains("overflowed"))
+            .count();
+        fs::remove_dir_all(out_dir).unwrap();
+
+        res.unwrap();
+        assert!(crashes > 0);
+    }
+}
@@ -0,0 +1,140 @@
+#![allow(clippy::needless_return)]
+
+use std::collections::VecDeque;
+
+use super::{Computer, Event, MemoryWrite, Opcode, bigint::BigInt};
+
+// Undo log of executed instructions, oldest first. Keeps at most `limit`
+// entries and forgets the oldest ones past that.
+#[derive(Clone, Debug)]
+pub(crate) struct History {
+    entries: VecDeque<Entry>,
+    limit: usize,
+}
+
+// State needed to undo one instruction.
+#[derive(Clone, Debug)]
+pub(crate) struct Entry {
+    pub(crate) ip: u64,
+    pub(crate) rel_base: i64,
+    pub(crate) write: Option<MemoryWrite>,
+    // What the written cell held when that didn't fit in `write.old`.
+    pub(crate) old_big: Option<BigInt>,
+    pub(crate) input: Option<i64>,
+}
+
+impl History {
+    fn new(limit: usize) -> Self {
+        return History {
+            entries: VecDeque::new(),
+            limit,
+        };
+    }
+
+    pub(crate) fn record(&mut self, event: &Event, rel_base: i64, old_big: Option<BigInt>) {
+        if self.limit == 0 {
+            return;
+        }
+
+        if self.entries.len() == self.limit {
+            self.entries.pop_front();
+        }
+
+        let input = match event.instruction.opcode {
+            Opcode::Input => event.write.map(|w| w.new),
+            _ => None,
+        };
+
+        self.entries.push_back(Entry {
+            ip: event.ip,
+            rel_base,
+            write: event.write,
+            old_big,
+            input,
+        });
+    }
+}
+
+impl Computer {
+    pub(crate) fn enable_history(&mut self, limit: usize) {
+        if self.history.is_none() {
+            self.history = Some(History::new(limit));
+        }
+    }
+
+    pub(crate) fn history_len(&self) -> usize {
+        return self.history.as_ref().map_or(0, |h| h.entries.len());
+    }
+
+    // Undoes the last executed instruction. An undone input goes back to
+    // `pending_input`, so executing forward again replays the same values.
+    pub(crate) fn step_back(&mut self) -> Option<Entry> {
+        let entry = self.history.as_mut()?.entries.pop_back()?;
+
+        if let Some(write) = entry.write {
+            match &entry.old_big {
+                Some(old) => self.memory.write_big(write.addr, old.clone()),
+                None => self.memory.write(write.addr, write.old),
+            }
+        }
+        if let Some(input) = entry.input {
+            self.pending_input.push_front(input);
+        }
+        self.ip = entry.ip;
+        self.rel_base = entry.rel_base;
+
+        return Some(entry);
+    }
+
+    // Returns how many instructions were undone.
+    pub(crate) fn rewind(&mut self, steps: usize) -> usize {
+        let mut undone = 0;
+        while undone < steps && self.step_back().is_some() {
+            undone += 1;
+        }
+
+        return undone;
+    }
+
+    // Rewinds to just before the last instruction that wrote to `addr`.
+    // Nothing changes if there's no such write in the history.
+    pub(crate) fn rewind_to_write(&mut self, addr: u64) -> Option<usize> {
+        let entries = &self.history.as_ref()?.entries;
+        let pos = entries.iter()
+            .rposition(|e| e.write.is_some_and(|w| w.addr == addr))?;
+
+        return Some(self.rewind(entries.len() - pos));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::computer::{Arithmetic, asm::assemble_computer, bigint::BigInt};
+
+    #[test]
+    fn undo_restores_big_cells() {
+        let mut computer = assemble_computer("
+                    MUL [x], #2, [x]
+                    MUL [x], #2, [x]
+                    HLT
+            x:      .data 9223372036854775807
+        ").unwrap();
+        computer.arithmetic = Arithmetic::BigInt;
+        computer.enable_history(10);
+        computer.run(std::iter::empty()).unwrap();
+
+        let max = BigInt::from(i64::MAX);
+        let two = BigInt::from(2);
+        let doubled = &max * &two;
+        assert_eq!(computer.memory.read_big(9), &doubled * &two);
+
+        computer.rewind(2);
+        assert_eq!(computer.ip, 4);
+        assert_eq!(computer.memory.read_big(9), doubled);
+
+        computer.rewind(1);
+        assert_eq!(computer.ip, 0);
+        assert_eq!(computer.memory.read_big(9), max);
+        assert!(!computer.memory.has_big_cells());
+    }
+}
@@ -0,0 +1,36 @@
+#![allow(clippy::needless_return)]
+
+// A device the VM reads its inputs from and writes its outputs to, driven by
+// `Computer::run_io` one value at a time.
+pub(crate) trait IntcodeIo {
+    // Next input value. `None` pauses the VM with `Status::PausedForInput`.
+    fn read(&mut self) -> Option<i64>;
+
+    fn write(&mut self, val: i64);
+}
+
+// Reads inputs from an iterator and collects every output, the way `run`
+// has always worked.
+pub(crate) struct IterIo<I> {
+    inputs: I,
+    pub(crate) outputs: Vec<i64>,
+}
+
+impl<I: Iterator<Item=i64>> IterIo<I> {
+    pub(crate) fn new(inputs: I) -> Self {
+        return IterIo {
+            inputs,
+            outputs: Vec::new(),
+        };
+    }
+}
+
+impl<I: Iterator<Item=i64>> IntcodeIo for IterIo<I> {
+    fn read(&mut self) -> Option<i64> {
+        return self.inputs.next();
+    }
+
+    fn write(&mut self, val: i64) {
+        self.outputs.push(val);
+    }
+}
@@ -0,0 +1,120 @@
+#![allow(clippy::needless_return)]
+
+use std::collections::HashMap;
+
+use super::{Arithmetic, Computer, Event};
+
+// Finds programs stuck in a loop without I/O by looking for a state that
+// repeats exactly, using Brent's cycle detection: the state at a checkpoint
+// is compared against every following one, and the checkpoint moves forward
+// after 1, 2, 4, ... steps. Memory only has to be compared on cells written
+// since the checkpoint, so every step costs O(1).
+#[derive(Clone, Debug, Default)]
+pub(crate) struct LivelockDetector {
+    ip: u64,
+    rel_base: i64,
+    // Value at the checkpoint of every cell written since.
+    saved: HashMap<u64, i64>,
+    // How many of the saved cells currently hold a different value.
+    changed: usize,
+    steps: u64,
+    period: u64,
+}
+
+impl LivelockDetector {
+    fn reset(&mut self, computer: &Computer) {
+        self.checkpoint(computer);
+        self.period = 1;
+    }
+
+    fn checkpoint(&mut self, computer: &Computer) {
+        self.ip = computer.ip;
+        self.rel_base = computer.rel_base;
+        self.saved.clear();
+        self.changed = 0;
+        self.steps = 0;
+    }
+
+    // Called after every executed instruction, returns whether the program
+    // is back in the checkpointed state. `io` is whether the instruction
+    // consumed input or wrote output, which no state comparison can see.
+    pub(crate) fn record(&mut self, computer: &Computer, event: &Event, io: bool) -> bool {
+        // Big integers only show up wrapped in the recorded writes, so they
+        // can't be compared.
+        let big = computer.arithmetic == Arithmetic::BigInt && computer.memory.has_big_cells();
+        if io || big {
+            self.reset(computer);
+            return false;
+        }
+
+        if let Some(write) = event.write {
+            let saved = *self.saved.entry(write.addr).or_insert(write.old);
+            let was_changed = write.old != saved;
+            let is_changed = write.new != saved;
+            if is_changed && !was_changed {
+                self.changed += 1;
+            } else if was_changed && !is_changed {
+                self.changed -= 1;
+            }
+        }
+        self.steps += 1;
+
+        if self.changed == 0 && computer.ip == self.ip && computer.rel_base == self.rel_base {
+            return true;
+        }
+
+        if self.steps == self.period {
+            self.checkpoint(computer);
+            self.period *= 2;
+        }
+
+        return false;
+    }
+}
+
+impl Computer {
+    // Makes `run` stop with `Status::Livelocked` once the program provably
+    // loops forever without doing any I/O.
+    pub(crate) fn enable_livelock_detection(&mut self) {
+        if self.livelock.is_none() {
+            let mut detector = LivelockDetector::default();
+            detector.reset(self);
+            self.livelock = Some(detector);
+        }
+    }
+
+    // Forgets the checkpoint, since memory may have been changed from outside
+    // the program since it was taken.
+    pub(super) fn reset_livelock_detection(&mut self) {
+        if let Some(mut detector) = self.livelock.take() {
+            detector.reset(self);
+            self.livelock = Some(detector);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::computer::{Computer, Flow, Status, opcodes};
+
+    #[test]
+    fn registered_io_is_progress() {
+        // `JT #1, #0` on its own.
+        let mut computer = Computer::from_program(&[1105, 1, 0]);
+        computer.enable_livelock_detection();
+        let output = computer.run_with_budget(std::iter::empty(), 100).unwrap();
+        assert_eq!(output.status, Status::Livelocked);
+
+        opcodes::register(77, "TICK", 0, &[], |_, io, _| {
+            io.write(1);
+            return Ok(Flow::Next);
+        }).unwrap();
+
+        // `TICK`, then the same jump back to it.
+        let mut computer = Computer::from_program(&[77, 1105, 1, 0]);
+        computer.enable_livelock_detection();
+        let output = computer.run_with_budget(std::iter::empty(), 100).unwrap();
+        assert_eq!(output.status, Status::BudgetExhausted);
+        assert_eq!(output.outputs, vec![1; 50]);
+    }
+}
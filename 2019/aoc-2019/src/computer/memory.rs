@@ -0,0 +1,275 @@
+#![allow(clippy::needless_return)]
+
+use std::{collections::HashMap, sync::atomic::{AtomicBool, AtomicU8, Ordering}};
+
+use super::{ComputerError, Decoded, MAX_PARAMS, bigint::BigInt, decode};
+
+// Addresses below this live in a vector, everything above in a map.
+const DENSE_LIMIT: u64 = 1 << 16;
+
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum MemoryBackend {
+    // Vector for the low addresses programs actually use, with a map for
+    // far-away ones.
+    Dense = 0,
+    // Every cell in a map.
+    Sparse = 1,
+}
+
+static DEFAULT_BACKEND: AtomicU8 = AtomicU8::new(MemoryBackend::Dense as u8);
+
+// Backend used by `Memory::new`, mostly for comparing them.
+pub(crate) fn set_default_backend(backend: MemoryBackend) {
+    DEFAULT_BACKEND.store(backend as u8, Ordering::Relaxed);
+}
+
+static DEFAULT_DECODE_CACHE: AtomicBool = AtomicBool::new(true);
+
+pub(crate) fn set_default_decode_cache(enabled: bool) {
+    DEFAULT_DECODE_CACHE.store(enabled, Ordering::Relaxed);
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct Memory {
+    dense: Vec<i64>,
+    sparse: HashMap<u64, i64>,
+    backend: MemoryBackend,
+    cache: Option<DecodeCache>,
+    // Values that don't fit in an i64, only written by big-integer
+    // arithmetic. The regular cell holds the value wrapped to 64 bits.
+    big: HashMap<u64, BigInt>,
+}
+
+// Decoded instructions by address, for addresses below `DENSE_LIMIT`.
+// `code` marks every cell covered by an instruction that was ever cached,
+// so writes only have to look for stale entries when they hit code. Marks are
+// never cleared since instructions may overlap.
+#[derive(Clone, Debug, Default)]
+struct DecodeCache {
+    decoded: Vec<Option<Decoded>>,
+    code: Vec<bool>,
+}
+
+impl DecodeCache {
+    fn get(&self, addr: u64) -> Option<Decoded> {
+        return *self.decoded.get(addr as usize)?;
+    }
+
+    fn insert(&mut self, addr: u64, decoded: Decoded) {
+        let start = addr as usize;
+        let end = start + decoded.size() as usize;
+        if end > self.code.len() {
+            self.code.resize(end, false);
+            self.decoded.resize(end, None);
+        }
+
+        self.decoded[start] = Some(decoded);
+        self.code[start..end].fill(true);
+    }
+
+    fn invalidate(&mut self, addr: u64) {
+        if !self.code.get(addr as usize).copied().unwrap_or(false) {
+            return;
+        }
+
+        let first = addr.saturating_sub(MAX_PARAMS as u64);
+        for start in first..=addr {
+            let entry = &mut self.decoded[start as usize];
+            if entry.is_some_and(|d| start + d.size() > addr) {
+                *entry = None;
+            }
+        }
+    }
+}
+
+impl Memory {
+    pub fn new() -> Self {
+        let backend = match DEFAULT_BACKEND.load(Ordering::Relaxed) {
+            0 => MemoryBackend::Dense,
+            _ => MemoryBackend::Sparse,
+        };
+
+        return Memory::with_backend(backend);
+    }
+
+    pub fn with_backend(backend: MemoryBackend) -> Self {
+        let cache = if DEFAULT_DECODE_CACHE.load(Ordering::Relaxed) {
+            Some(DecodeCache::default())
+        } else {
+            None
+        };
+
+        return Self {
+            dense: Vec::new(),
+            sparse: HashMap::new(),
+            backend,
+            cache,
+            big: HashMap::new(),
+        };
+    }
+
+    pub fn read(&self, addr: u64) -> i64 {
+        if addr < self.dense.len() as u64 {
+            return self.dense[addr as usize];
+        }
+
+        return *self.sparse.get(&addr).unwrap_or(&0);
+    }
+
+    pub fn write(&mut self, addr: u64, val: i64) {
+        if let Some(cache) = &mut self.cache {
+            cache.invalidate(addr);
+        }
+        if !self.big.is_empty() {
+            self.big.remove(&addr);
+        }
+
+        if self.backend == MemoryBackend::Dense && addr < DENSE_LIMIT {
+            let idx = addr as usize;
+            if idx >= self.dense.len() {
+                self.dense.resize(idx + 1, 0);
+            }
+            self.dense[idx] = val;
+            return;
+        }
+
+        self.sparse.insert(addr, val);
+    }
+
+    pub(crate) fn has_big_cells(&self) -> bool {
+        return !self.big.is_empty();
+    }
+
+    pub(crate) fn is_big(&self, addr: u64) -> bool {
+        return self.big.contains_key(&addr);
+    }
+
+    pub(crate) fn read_big(&self, addr: u64) -> BigInt {
+        return match self.big.get(&addr) {
+            Some(val) => val.clone(),
+            None => BigInt::from(self.read(addr)),
+        };
+    }
+
+    pub(crate) fn write_big(&mut self, addr: u64, val: BigInt) {
+        self.write(addr, val.wrapping_i64());
+        if val.to_i64().is_none() {
+            self.big.insert(addr, val);
+        }
+    }
+
+    // Cells holding a value that doesn't fit in an i64, ordered by address.
+    pub(crate) fn big_cells(&self) -> Vec<(u64, BigInt)> {
+        let mut cells = self.big.iter()
+            .map(|(addr, val)| (*addr, val.clone()))
+            .collect::<Vec<_>>();
+        cells.sort();
+
+        return cells;
+    }
+
+    // Same as `decode`, but reuses the instruction decoded at `addr` last
+    // time unless it has been written to since.
+    pub(crate) fn decode(&mut self, addr: u64) -> Result<Decoded, ComputerError> {
+        let Some(cache) = &self.cache else {
+            return decode(self, addr);
+        };
+        if let Some(decoded) = cache.get(addr) {
+            return Ok(decoded);
+        }
+
+        let decoded = decode(self, addr)?;
+        if addr + decoded.size() <= DENSE_LIMIT
+                && let Some(cache) = &mut self.cache {
+            cache.insert(addr, decoded);
+        }
+
+        return Ok(decoded);
+    }
+
+    // Cells holding a non-zero value, ordered by address.
+    pub fn cells(&self) -> Vec<(u64, i64)> {
+        let mut cells = self.dense.iter()
+            .enumerate()
+            .map(|(addr, val)| (addr as u64, *val))
+            .chain(self.sparse.iter().map(|(addr, val)| (*addr, *val)))
+            .filter(|(_, val)| *val != 0)
+            .collect::<Vec<_>>();
+        cells.sort();
+
+        return cells;
+    }
+
+    // One past the highest address holding a value.
+    pub fn len(&self) -> u64 {
+        let sparse_len = self.sparse.keys().max().map_or(0, |addr| addr + 1);
+        return sparse_len.max(self.dense.len() as u64);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::computer::asm::assemble_computer;
+
+    // Held by tests that change the process-wide defaults.
+    static DEFAULTS: Mutex<()> = Mutex::new(());
+
+    // Runs `target` three times, patching its operand before the second run
+    // and its opcode word before the third.
+    const SELF_MODIFYING: &str = "
+        target: OUT #1
+                ADD [n], #1, [n]
+                EQ [n], #1, [t]
+                JT [t], #second
+                EQ [n], #2, [t]
+                JT [t], #third
+                HLT
+        second: ADD #2, #0, [target+1]
+                JT #1, #target
+        third:  ADD #4, #0, [target]
+                ADD #seven, #0, [target+1]
+                JT #1, #target
+        n:      .data 0
+        t:      .data 0
+        seven:  .data 7
+    ";
+
+    #[test]
+    fn self_modifying_code() {
+        let _defaults = DEFAULTS.lock().unwrap();
+        for cached in [false, true] {
+            set_default_decode_cache(cached);
+            let mut computer = assemble_computer(SELF_MODIFYING).unwrap();
+            assert_eq!(computer.memory.cache.is_some(), cached);
+
+            let output = computer.run(std::iter::empty()).unwrap();
+            assert_eq!(output.outputs, vec![1, 2, 7], "cached: {cached}");
+        }
+        set_default_decode_cache(true);
+    }
+
+    #[test]
+    fn writes_invalidate_whole_instructions() {
+        let mut memory = Memory::with_backend(MemoryBackend::Dense);
+        memory.cache = Some(DecodeCache::default());
+        for (addr, val) in [1101, 1, 2, 10, 99].into_iter().enumerate() {
+            memory.write(addr as u64, val);
+        }
+
+        // The last parameter is `MAX_PARAMS` cells past the start.
+        memory.decode(0).unwrap();
+        memory.write(MAX_PARAMS as u64, 20);
+        assert_eq!(memory.decode(0).unwrap().params[2].val, 20);
+
+        // The next instruction doesn't touch this one.
+        memory.decode(MAX_PARAMS as u64 + 1).unwrap();
+        assert!(memory.cache.as_ref().unwrap().get(0).is_some());
+        memory.write(MAX_PARAMS as u64 + 1, 4);
+        assert!(memory.cache.as_ref().unwrap().get(0).is_some());
+        assert!(memory.cache.as_ref().unwrap().get(MAX_PARAMS as u64 + 1).is_none());
+    }
+}
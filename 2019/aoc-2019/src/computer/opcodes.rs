@@ -0,0 +1,316 @@
+#![allow(clippy::needless_return)]
+
+use std::sync::{Mutex, OnceLock};
+
+use anyhow::{Result, bail};
+
+use super::{
+    Computer, ComputerError, Instruction, IntcodeIo, MAX_PARAMS, Opcode, checked_rel_base,
+};
+
+// Where execution goes after an instruction.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum Flow {
+    Next,
+    Jump(u64),
+    Halt,
+    // Pauses with `ip` still on the instruction, which runs again once
+    // there's input.
+    NeedInput,
+}
+
+// Runs an instruction with `ip` still pointing at it.
+pub(crate) type Exec = dyn Fn(&mut Computer, &mut dyn IntcodeIo, &Instruction)
+    -> std::result::Result<Flow, ComputerError> + Send + Sync;
+
+// Everything the VM knows about an opcode. The built-in ones are below,
+// others can be added with `register` before running anything that uses
+// them, e.g. the extensions at the end of this file.
+pub(crate) struct OpcodeSpec {
+    pub(crate) code: u8,
+    pub(crate) mnemonic: &'static str,
+    pub(crate) arity: u64,
+    // Parameters that are addresses the instruction writes to. They're
+    // resolved to the address instead of the value it holds.
+    pub(crate) write_params: &'static [usize],
+    pub(crate) exec: &'static Exec,
+}
+
+static ADD: OpcodeSpec = OpcodeSpec {
+    code: 1,
+    mnemonic: "ADD",
+    arity: 3,
+    write_params: &[2],
+    exec: &add,
+};
+static MUL: OpcodeSpec = OpcodeSpec {
+    code: 2,
+    mnemonic: "MUL",
+    arity: 3,
+    write_params: &[2],
+    exec: &mul,
+};
+static INPUT: OpcodeSpec = OpcodeSpec {
+    code: 3,
+    mnemonic: "IN",
+    arity: 1,
+    write_params: &[0],
+    exec: &input,
+};
+static OUTPUT: OpcodeSpec = OpcodeSpec {
+    code: 4,
+    mnemonic: "OUT",
+    arity: 1,
+    write_params: &[],
+    exec: &output,
+};
+static JUMP_IF_TRUE: OpcodeSpec = OpcodeSpec {
+    code: 5,
+    mnemonic: "JT",
+    arity: 2,
+    write_params: &[],
+    exec: &jump_if_true,
+};
+static JUMP_IF_FALSE: OpcodeSpec = OpcodeSpec {
+    code: 6,
+    mnemonic: "JF",
+    arity: 2,
+    write_params: &[],
+    exec: &jump_if_false,
+};
+static LESS_THAN: OpcodeSpec = OpcodeSpec {
+    code: 7,
+    mnemonic: "LT",
+    arity: 3,
+    write_params: &[2],
+    exec: &less_than,
+};
+static EQUALS: OpcodeSpec = OpcodeSpec {
+    code: 8,
+    mnemonic: "EQ",
+    arity: 3,
+    write_params: &[2],
+    exec: &equals,
+};
+static ADJ_REL_BASE: OpcodeSpec = OpcodeSpec {
+    code: 9,
+    mnemonic: "ARB",
+    arity: 1,
+    write_params: &[],
+    exec: &adj_rel_base,
+};
+static HALT: OpcodeSpec = OpcodeSpec {
+    code: 99,
+    mnemonic: "HLT",
+    arity: 0,
+    write_params: &[],
+    exec: &halt,
+};
+
+// Registered opcodes by code. Set once and never removed, so looking them up
+// while running doesn't need a lock.
+static CUSTOM: [OnceLock<OpcodeSpec>; 100] = [const { OnceLock::new() }; 100];
+// Held while registering, so two opcodes can't take the same mnemonic.
+static REGISTERING: Mutex<()> = Mutex::new(());
+
+pub(crate) fn spec(opcode: Opcode) -> &'static OpcodeSpec {
+    return match opcode {
+        Opcode::Add => &ADD,
+        Opcode::Mul => &MUL,
+        Opcode::Input => &INPUT,
+        Opcode::Output => &OUTPUT,
+        Opcode::JumpIfTrue => &JUMP_IF_TRUE,
+        Opcode::JumpIfFalse => &JUMP_IF_FALSE,
+        Opcode::LessThan => &LESS_THAN,
+        Opcode::Equals => &EQUALS,
+        Opcode::AdjRelBase => &ADJ_REL_BASE,
+        Opcode::Halt => &HALT,
+        Opcode::Custom(code) => CUSTOM[code as usize].get().unwrap(),
+    };
+}
+
+// Runs `spec(opcode).exec`. Built-in opcodes are called directly rather
+// than through the pointer, so they get inlined into the interpreter loop.
+pub(crate) fn exec(computer: &mut Computer, io: &mut dyn IntcodeIo, instruction: &Instruction)
+        -> std::result::Result<Flow, ComputerError> {
+    return match instruction.opcode {
+        Opcode::Add => add(computer, io, instruction),
+        Opcode::Mul => mul(computer, io, instruction),
+        Opcode::Input => input(computer, io, instruction),
+        Opcode::Output => output(computer, io, instruction),
+        Opcode::JumpIfTrue => jump_if_true(computer, io, instruction),
+        Opcode::JumpIfFalse => jump_if_false(computer, io, instruction),
+        Opcode::LessThan => less_than(computer, io, instruction),
+        Opcode::Equals => equals(computer, io, instruction),
+        Opcode::AdjRelBase => adj_rel_base(computer, io, instruction),
+        Opcode::Halt => halt(computer, io, instruction),
+        Opcode::Custom(_) => (spec(instruction.opcode).exec)(computer, io, instruction),
+    };
+}
+
+// The opcode with the given code, built in or registered.
+pub(crate) fn lookup(code: u8) -> Option<Opcode> {
+    if let Some(opcode) = Opcode::ALL.into_iter().find(|op| spec(*op).code == code) {
+        return Some(opcode);
+    }
+
+    return CUSTOM.get(code as usize)?.get().map(|spec| Opcode::Custom(spec.code));
+}
+
+// Every opcode, built-in ones first.
+pub(crate) fn all() -> Vec<Opcode> {
+    let custom = CUSTOM.iter()
+        .filter_map(|spec| spec.get())
+        .map(|spec| Opcode::Custom(spec.code));
+    return Opcode::ALL.into_iter().chain(custom).collect();
+}
+
+// Adds an opcode for every VM and tool, e.g. the disassembler, from now on.
+// `exec` gets the operands the same way built-in instructions do.
+pub(crate) fn register(
+    code: u8,
+    mnemonic: &'static str,
+    arity: u64,
+    write_params: &'static [usize],
+    exec: impl Fn(&mut Computer, &mut dyn IntcodeIo, &Instruction)
+        -> std::result::Result<Flow, ComputerError> + Send + Sync + 'static,
+) -> Result<Opcode> {
+    if code > 99 {
+        bail!("Op code {code} doesn't fit in two digits");
+    }
+    if arity > MAX_PARAMS as u64 {
+        bail!("`{mnemonic}` can't have more than {MAX_PARAMS} parameters");
+    }
+    if let Some(idx) = write_params.iter().find(|idx| **idx as u64 >= arity) {
+        bail!("`{mnemonic}` has no parameter {idx} to write to");
+    }
+
+    let _registering = REGISTERING.lock().unwrap();
+    let taken = all().into_iter()
+        .map(spec)
+        .find(|spec| spec.code == code || spec.mnemonic.eq_ignore_ascii_case(mnemonic));
+    if let Some(spec) = taken {
+        bail!("`{mnemonic}` ({code}) clashes with `{}` ({})", spec.mnemonic, spec.code);
+    }
+
+    let spec = OpcodeSpec {
+        code,
+        mnemonic,
+        arity,
+        write_params,
+        // Leaked, registered specs are never removed.
+        exec: Box::leak(Box::new(exec)),
+    };
+    // Can't be taken already, that's checked above under the lock.
+    let _ = CUSTOM[code as usize].set(spec);
+
+    return Ok(Opcode::Custom(code));
+}
+
+fn add(computer: &mut Computer, _io: &mut dyn IntcodeIo, instruction: &Instruction)
+        -> std::result::Result<Flow, ComputerError> {
+    let &[a, b, pos] = instruction.operands() else { unreachable!() };
+    computer.arithmetic_op(Opcode::Add, a, b, pos as u64)?;
+    return Ok(Flow::Next);
+}
+
+fn mul(computer: &mut Computer, _io: &mut dyn IntcodeIo, instruction: &Instruction)
+        -> std::result::Result<Flow, ComputerError> {
+    let &[a, b, pos] = instruction.operands() else { unreachable!() };
+    computer.arithmetic_op(Opcode::Mul, a, b, pos as u64)?;
+    return Ok(Flow::Next);
+}
+
+fn input(computer: &mut Computer, io: &mut dyn IntcodeIo, instruction: &Instruction)
+        -> std::result::Result<Flow, ComputerError> {
+    let Some(val) = computer.pending_input.pop_front().or_else(|| io.read()) else {
+        return Ok(Flow::NeedInput);
+    };
+
+    computer.memory.write(instruction.operands()[0] as u64, val);
+    return Ok(Flow::Next);
+}
+
+fn output(_computer: &mut Computer, io: &mut dyn IntcodeIo, instruction: &Instruction)
+        -> std::result::Result<Flow, ComputerError> {
+    io.write(instruction.operands()[0]);
+    return Ok(Flow::Next);
+}
+
+fn jump_if_true(computer: &mut Computer, _io: &mut dyn IntcodeIo, instruction: &Instruction)
+        -> std::result::Result<Flow, ComputerError> {
+    let &[val, pos] = instruction.operands() else { unreachable!() };
+    if val != 0 {
+        return Ok(Flow::Jump(computer.address(instruction.opcode, pos)?));
+    }
+    return Ok(Flow::Next);
+}
+
+fn jump_if_false(computer: &mut Computer, _io: &mut dyn IntcodeIo, instruction: &Instruction)
+        -> std::result::Result<Flow, ComputerError> {
+    let &[val, pos] = instruction.operands() else { unreachable!() };
+    if val == 0 {
+        return Ok(Flow::Jump(computer.address(instruction.opcode, pos)?));
+    }
+    return Ok(Flow::Next);
+}
+
+fn less_than(computer: &mut Computer, _io: &mut dyn IntcodeIo, instruction: &Instruction)
+        -> std::result::Result<Flow, ComputerError> {
+    let &[a, b, pos] = instruction.operands() else { unreachable!() };
+    let res = computer.compare(a, b)?.is_lt();
+    computer.memory.write(pos as u64, res as i64);
+    return Ok(Flow::Next);
+}
+
+fn equals(computer: &mut Computer, _io: &mut dyn IntcodeIo, instruction: &Instruction)
+        -> std::result::Result<Flow, ComputerError> {
+    let &[a, b, pos] = instruction.operands() else { unreachable!() };
+    let res = computer.compare(a, b)?.is_eq();
+    computer.memory.write(pos as u64, res as i64);
+    return Ok(Flow::Next);
+}
+
+fn adj_rel_base(computer: &mut Computer, _io: &mut dyn IntcodeIo, instruction: &Instruction)
+        -> std::result::Result<Flow, ComputerError> {
+    let operand = instruction.operands()[0];
+    computer.rel_base = checked_rel_base(computer.ip, computer.rel_base, operand)?;
+    return Ok(Flow::Next);
+}
+
+fn halt(_computer: &mut Computer, _io: &mut dyn IntcodeIo, _instruction: &Instruction)
+        -> std::result::Result<Flow, ComputerError> {
+    return Ok(Flow::Halt);
+}
+
+// Opcodes that aren't part of Intcode, for experiments. Enabled by name with
+// the global `--ext` flag.
+pub(crate) fn register_extension(name: &str) -> Result<Opcode> {
+    return match name {
+        "dbg" => register(50, "DBG", 1, &[], debug_print),
+        "hltc" => register(98, "HLTC", 1, &[], halt_with_code),
+        _ => bail!("Unknown extension `{name}`, expected `dbg` or `hltc`"),
+    };
+}
+
+// `DBG a` prints `a` to stderr, next to the address of the instruction.
+fn debug_print(computer: &mut Computer, _io: &mut dyn IntcodeIo, instruction: &Instruction)
+        -> std::result::Result<Flow, ComputerError> {
+    eprintln!("[{}] {}", computer.ip, instruction.operands()[0]);
+    return Ok(Flow::Next);
+}
+
+// `HLTC a` halts with exit code `a`. Any code other than 0 is an error.
+fn halt_with_code(computer: &mut Computer, _io: &mut dyn IntcodeIo, instruction: &Instruction)
+        -> std::result::Result<Flow, ComputerError> {
+    let code = instruction.operands()[0];
+    if code != 0 {
+        return Err(ComputerError::Exited {
+            ip: computer.ip,
+            rel_base: computer.rel_base,
+            opcode: instruction.opcode,
+            code,
+        });
+    }
+    return Ok(Flow::Halt);
+}
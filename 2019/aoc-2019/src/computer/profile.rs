@@ -0,0 +1,136 @@
+#![allow(clippy::needless_return)]
+
+use std::{collections::HashMap, sync::{Arc, Mutex}};
+
+use anyhow::{Context, Result, anyhow, bail};
+
+use super::{
+    Computer, ComputerError, Event, Observer, Opcode, attach_global_observer, disasm,
+};
+
+// Execution counts of every VM it's attached to, added up.
+#[derive(Debug, Default)]
+pub(crate) struct Profiler {
+    instructions: u64,
+    by_addr: HashMap<u64, u64>,
+    by_opcode: HashMap<u8, u64>,
+    // Text of each executed instruction, taken the first time it ran.
+    text: HashMap<u64, String>,
+    max_addr: u64,
+    inputs: u64,
+    outputs: u64,
+    halts: u64,
+    // Taken backward jumps by (target, jump address), i.e. loop iterations.
+    loops: HashMap<(u64, u64), u64>,
+}
+
+impl Profiler {
+    pub(crate) fn report(&self, top: usize) -> String {
+        let total = self.instructions.max(1) as f64;
+        let percent = |count: u64| count as f64 * 100.0 / total;
+
+        let mut report = String::from("Profile:\n");
+        report += &format!("  instructions executed: {}\n", self.instructions);
+        report += &format!("  programs halted:       {}\n", self.halts);
+        report += &format!("  inputs read:           {}\n", self.inputs);
+        report += &format!("  outputs written:       {}\n", self.outputs);
+        report += &format!("  highest address:       {}\n", self.max_addr);
+
+        report += "\nBy opcode:\n";
+        for (opcode, count) in sorted_by_count(&self.by_opcode, usize::MAX) {
+            let mnemonic = Opcode::try_from(opcode as i64).map_or("?", |op| op.mnemonic());
+            report += &format!("  {mnemonic:<4} {count:>12} {:>6.2}%\n", percent(count));
+        }
+
+        report += &format!("\nHottest addresses (top {top}):\n");
+        for (addr, count) in sorted_by_count(&self.by_addr, top) {
+            let text = self.text.get(&addr).map_or("", |t| t.as_str());
+            report += &format!("  {addr:>6}: {text:<32} {count:>12} {:>6.2}%\n", percent(count));
+        }
+
+        report += &format!("\nHottest loops (top {top}):\n");
+        for ((target, from), count) in sorted_by_count(&self.loops, top) {
+            report += &format!("  {target:>6}..={from:<6} {count:>12} iteration(s)\n");
+        }
+
+        return report;
+    }
+}
+
+fn sorted_by_count<K: Copy + Ord>(counts: &HashMap<K, u64>, top: usize) -> Vec<(K, u64)> {
+    let mut counts = counts.iter()
+        .map(|(key, count)| (*key, *count))
+        .collect::<Vec<_>>();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    counts.truncate(top);
+
+    return counts;
+}
+
+impl Observer for Profiler {
+    fn before_step(&mut self, computer: &mut Computer) -> std::result::Result<(), ComputerError> {
+        let ip = computer.ip;
+        self.text.entry(ip)
+            .or_insert_with(|| disasm::line_at(&computer.memory, ip).text());
+
+        // Faults are reported by the VM itself once it gets to decoding.
+        if let Ok(decoded) = computer.memory.decode(ip) {
+            let mut max_addr = ip + decoded.size() - 1;
+            for idx in 0..decoded.params().len() {
+                if let Ok(Some(addr)) = decoded.param_addr(ip, idx, computer.rel_base) {
+                    max_addr = max_addr.max(addr);
+                }
+            }
+            self.max_addr = self.max_addr.max(max_addr);
+        }
+
+        return Ok(());
+    }
+
+    fn after_step(&mut self, computer: &Computer, event: &Event) {
+        self.instructions += 1;
+        *self.by_addr.entry(event.ip).or_default() += 1;
+        *self.by_opcode.entry(event.instruction.opcode.code()).or_default() += 1;
+
+        match event.instruction.opcode {
+            Opcode::Input => self.inputs += 1,
+            Opcode::Output => self.outputs += 1,
+            Opcode::Halt => self.halts += 1,
+            Opcode::JumpIfTrue | Opcode::JumpIfFalse
+                    if computer.ip <= event.ip => {
+                *self.loops.entry((computer.ip, event.ip)).or_default() += 1;
+            },
+            _ => {},
+        }
+    }
+}
+
+pub(crate) fn command(args: &[String]) -> Result<()> {
+    let usage = "Usage: profile <day> <input_file> [part] [--top n]";
+
+    let mut positional = Vec::new();
+    let mut top = 10;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "--top" {
+            let value = args.next().ok_or_else(|| anyhow!("Missing value for `--top`"))?;
+            top = value.parse::<usize>().context("Invalid number of entries")?;
+        } else {
+            positional.push(arg.clone());
+        }
+    }
+
+    if positional.len() < 2 {
+        bail!(usage);
+    }
+
+    let profiler = Arc::new(Mutex::new(Profiler::default()));
+    attach_global_observer(profiler.clone());
+
+    let res = crate::run_day_with_args(&positional);
+    println!();
+    print!("{}", profiler.lock().unwrap().report(top));
+
+    return res;
+}
@@ -0,0 +1,236 @@
+#![allow(clippy::needless_return)]
+
+use std::io::{self, BufRead, Write};
+
+use anyhow::{Context, Result, anyhow, bail};
+
+use super::{Computer, Step};
+
+const HELP: &str = "\
+Lines are sent as integers if they're numbers separated by spaces or commas,
+and as ASCII text followed by a newline otherwise, or always with `:ascii on`.
+
+Commands:
+  :peek <addr> [len]     dump memory
+  :poke <addr> <val>     write to memory
+  :send <val>...         send integers, even in ASCII mode
+  :ascii [on|off]        show or set whether outputs are rendered as text
+  :ip                    show ip and rel_base
+  :reset                 restart the program from scratch
+  :save <path>           save a snapshot of the VM
+  :load <path>           replace the VM with a saved snapshot
+  :help                  show this message
+  :quit                  leave";
+
+// Interactive session with a program: every line typed becomes input, and
+// the program runs until it needs more of it.
+struct Repl {
+    initial: Computer,
+    computer: Computer,
+    ascii: bool,
+    // Text output that isn't a full line yet.
+    line: String,
+}
+
+impl Repl {
+    fn new(mut computer: Computer, ascii: bool) -> Self {
+        // A program stuck in a loop would otherwise never give the prompt back.
+        computer.enable_livelock_detection();
+
+        return Repl {
+            initial: computer.clone(),
+            computer,
+            ascii,
+            line: String::new(),
+        };
+    }
+
+    fn run(&mut self) -> Result<()> {
+        let stdin = io::stdin();
+        let mut input = String::new();
+
+        self.resume();
+        loop {
+            print!("> ");
+            io::stdout().flush()?;
+
+            input.clear();
+            if stdin.lock().read_line(&mut input)? == 0 {
+                println!();
+                return Ok(());
+            }
+            let input = input.trim_end_matches(['\n', '\r']);
+
+            let res = match input.strip_prefix(':') {
+                Some(command) => self.command(command),
+                None => self.send(input),
+            };
+            match res {
+                Ok(true) => return Ok(()),
+                Ok(false) => {},
+                Err(e) => println!("error: {e:#}"),
+            }
+        }
+    }
+
+    // Runs until the program needs input it doesn't have.
+    fn resume(&mut self) {
+        loop {
+            match self.computer.step() {
+                Ok(Step::Output(val)) => self.show(val),
+                Ok(Step::NeedInput) => break,
+                Ok(Step::Halted) => {
+                    self.flush();
+                    println!("Halted at {}", self.computer.ip);
+                    break;
+                },
+                Err(e) => {
+                    self.flush();
+                    println!("error: {e}");
+                    break;
+                },
+            }
+        }
+        self.flush();
+    }
+
+    fn show(&mut self, val: i64) {
+        match u8::try_from(val) {
+            Ok(b'\n') if self.ascii => {
+                println!("{}", self.line);
+                self.line.clear();
+            },
+            Ok(ch) if self.ascii && (ch.is_ascii_graphic() || ch == b' ') => {
+                self.line.push(ch as char);
+            },
+            _ => {
+                self.flush();
+                println!("{val}");
+            },
+        }
+    }
+
+    fn flush(&mut self) {
+        if !self.line.is_empty() {
+            println!("{}", self.line);
+            self.line.clear();
+        }
+    }
+
+    fn send(&mut self, input: &str) -> Result<bool> {
+        let numbers = parse_values(input);
+        match numbers {
+            Ok(values) if !self.ascii && !values.is_empty() => {
+                for val in values {
+                    self.computer.push_input(val);
+                }
+            },
+            _ => {
+                if !input.is_ascii() {
+                    bail!("Only ASCII text can be sent");
+                }
+                for ch in input.bytes() {
+                    self.computer.push_input(ch as i64);
+                }
+                self.computer.push_input(b'\n' as i64);
+            },
+        }
+
+        self.resume();
+        return Ok(false);
+    }
+
+    // Returns whether to leave.
+    fn command(&mut self, command: &str) -> Result<bool> {
+        let words = command.split_whitespace().collect::<Vec<_>>();
+        let arg = |idx: usize| -> Result<i64> {
+            let word = words.get(idx)
+                .ok_or_else(|| anyhow!("Missing argument, see `:help`"))?;
+            return word.parse::<i64>()
+                .with_context(|| format!("`{word}` is not a number"));
+        };
+        let addr = |idx: usize| -> Result<u64> {
+            return u64::try_from(arg(idx)?).map_err(|_| anyhow!("Address can't be negative"));
+        };
+
+        match words.first().copied().unwrap_or("") {
+            "peek" => {
+                let start = addr(1)?;
+                let len = if words.len() > 2 { addr(2)? } else { 1 };
+                for a in start..start + len {
+                    println!("[{a}] = {}", self.computer.memory.read_big(a));
+                }
+            },
+            "poke" => {
+                self.computer.memory.write(addr(1)?, arg(2)?);
+            },
+            "send" => {
+                let values = parse_values(&words[1..].join(" "))?;
+                for val in values {
+                    self.computer.push_input(val);
+                }
+                self.resume();
+            },
+            "ascii" => {
+                match words.get(1).copied() {
+                    Some("on") => self.ascii = true,
+                    Some("off") => self.ascii = false,
+                    Some(w) => bail!("Expected `on` or `off`, got `{w}`"),
+                    None => {},
+                }
+                println!("ascii: {}", if self.ascii { "on" } else { "off" });
+            },
+            "ip" => {
+                println!("ip = {}, rel_base = {}", self.computer.ip, self.computer.rel_base);
+            },
+            "reset" => {
+                self.computer = self.initial.clone();
+                self.line.clear();
+                self.resume();
+            },
+            "save" => {
+                let path = words.get(1).ok_or_else(|| anyhow!("Missing snapshot path"))?;
+                self.computer.save(path)?;
+            },
+            "load" => {
+                let path = words.get(1).ok_or_else(|| anyhow!("Missing snapshot path"))?;
+                self.computer = Computer::load(path)?;
+                self.computer.enable_livelock_detection();
+                self.line.clear();
+                self.resume();
+            },
+            "help" => println!("{HELP}"),
+            "q" | "quit" => return Ok(true),
+            cmd => bail!("Unknown command `:{cmd}`, see `:help`"),
+        }
+
+        return Ok(false);
+    }
+}
+
+fn parse_values(text: &str) -> Result<Vec<i64>> {
+    return text.split([' ', ','])
+        .filter(|v| !v.is_empty())
+        .map(|v| v.parse::<i64>().with_context(|| format!("`{v}` is not a number")))
+        .collect();
+}
+
+pub(crate) fn command(args: &[String]) -> Result<()> {
+    let usage = "Usage: repl <input_file> [--ascii]";
+
+    let mut positional = Vec::new();
+    let mut ascii = false;
+    for arg in args {
+        match arg.as_str() {
+            "--ascii" => ascii = true,
+            _ => positional.push(arg),
+        }
+    }
+    if positional.is_empty() {
+        bail!(usage);
+    }
+
+    let computer = super::asm::load(positional[0])?;
+    println!("Type `:help` for the list of commands.");
+    return Repl::new(computer, ascii).run();
+}
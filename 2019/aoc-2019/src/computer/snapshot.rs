@@ -0,0 +1,179 @@
+#![allow(clippy::needless_return)]
+
+use std::fs;
+
+use anyhow::{Context, Result, anyhow, bail};
+
+use super::{Arithmetic, Computer, bigint::BigInt};
+
+// Snapshot files are plain text, one field per line:
+//
+//     intcode-snapshot 1
+//     ip 12
+//     rel_base 1000
+//     input 1,2,3
+//     memory 0 1,0,0,3,99
+//     memory 1000 5
+//     big 1001 -100000000000000000000
+//     arithmetic bigint
+//
+// Each `memory` line is a run of consecutive cells starting at the given
+// address. Cells missing from every run are zero. `big` lines hold values
+// that don't fit in an i64 and come after the memory runs. `arithmetic` is
+// left out for the default mode.
+pub(super) const HEADER: &str = "intcode-snapshot";
+const VERSION: u32 = 1;
+
+// Zero cells shorter than this don't split a memory run.
+const MAX_GAP: u64 = 32;
+
+impl Computer {
+    pub(crate) fn save(&self, path: &str) -> Result<()> {
+        let mut contents = format!("{HEADER} {VERSION}\n");
+        contents += &format!("ip {}\n", self.ip);
+        contents += &format!("rel_base {}\n", self.rel_base);
+        contents += format!("input {}", join(self.pending_input.iter())).trim_end();
+        contents += "\n";
+
+        for (start, values) in memory_runs(&self.memory.cells()) {
+            contents += &format!("memory {start} {}\n", join(values.iter()));
+        }
+        for (addr, val) in self.memory.big_cells() {
+            contents += &format!("big {addr} {val}\n");
+        }
+        if self.arithmetic != Arithmetic::default() {
+            contents += &format!("arithmetic {}\n", self.arithmetic.name());
+        }
+
+        fs::write(path, contents)
+            .with_context(|| format!("Couldn't write snapshot `{path}`"))?;
+        return Ok(());
+    }
+
+    pub(crate) fn load(path: &str) -> Result<Self> {
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("Couldn't read snapshot `{path}`"))?;
+        return parse_snapshot(&contents)
+            .with_context(|| format!("Invalid snapshot `{path}`"));
+    }
+}
+
+fn parse_snapshot(contents: &str) -> Result<Computer> {
+    let mut lines = contents.lines();
+
+    let header = lines.next().unwrap_or_default();
+    let version = header.strip_prefix(HEADER)
+        .ok_or_else(|| anyhow!("Missing `{HEADER}` header"))?
+        .trim()
+        .parse::<u32>()
+        .context("Invalid snapshot version")?;
+    if version != VERSION {
+        bail!("Unsupported snapshot version {version}, expected {VERSION}");
+    }
+
+    let mut computer = Computer::from_program(&[]);
+
+    for (idx, line) in lines.enumerate() {
+        let line_no = idx + 2;
+        let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+
+        match key {
+            "ip" => {
+                computer.ip = value.parse()
+                    .with_context(|| format!("Line {line_no}: invalid ip"))?;
+            },
+            "rel_base" => {
+                computer.rel_base = value.parse()
+                    .with_context(|| format!("Line {line_no}: invalid rel_base"))?;
+            },
+            "input" => {
+                computer.pending_input = parse_values(value)
+                    .with_context(|| format!("Line {line_no}: invalid input"))?
+                    .into();
+            },
+            "memory" => {
+                let (start, values) = value.split_once(' ')
+                    .ok_or_else(|| anyhow!("Line {line_no}: expected `memory <addr> <values>`"))?;
+                let start = start.parse::<u64>()
+                    .with_context(|| format!("Line {line_no}: invalid address"))?;
+                let values = parse_values(values)
+                    .with_context(|| format!("Line {line_no}: invalid memory values"))?;
+
+                for (offset, val) in values.into_iter().enumerate() {
+                    let Some(addr) = start.checked_add(offset as u64) else {
+                        bail!("Line {line_no}: memory runs past the last address");
+                    };
+                    computer.memory.write(addr, val);
+                }
+            },
+            "big" => {
+                let (addr, val) = value.split_once(' ')
+                    .ok_or_else(|| anyhow!("Line {line_no}: expected `big <addr> <value>`"))?;
+                let addr = addr.parse::<u64>()
+                    .with_context(|| format!("Line {line_no}: invalid address"))?;
+                let val = val.parse::<BigInt>()
+                    .with_context(|| format!("Line {line_no}: invalid value"))?;
+
+                computer.memory.write_big(addr, val);
+            },
+            "arithmetic" => {
+                computer.arithmetic = Arithmetic::from_name(value)
+                    .ok_or_else(|| anyhow!("Line {line_no}: unknown arithmetic `{value}`"))?;
+            },
+            "" => {},
+            key => bail!("Line {line_no}: unknown field `{key}`"),
+        }
+    }
+
+    return Ok(computer);
+}
+
+fn memory_runs(cells: &[(u64, i64)]) -> Vec<(u64, Vec<i64>)> {
+    let mut runs: Vec<(u64, Vec<i64>)> = Vec::new();
+
+    for &(addr, val) in cells {
+        match runs.last_mut() {
+            Some((start, values)) if addr - (*start + values.len() as u64) < MAX_GAP => {
+                let end = *start + values.len() as u64;
+                values.extend(std::iter::repeat_n(0, (addr - end) as usize));
+                values.push(val);
+            },
+            _ => runs.push((addr, vec![val])),
+        }
+    }
+
+    return runs;
+}
+
+fn parse_values(text: &str) -> Result<Vec<i64>> {
+    if text.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let values = text.split(",")
+        .map(|v| v.trim().parse::<i64>())
+        .collect::<Result<Vec<_>, _>>()?;
+    return Ok(values);
+}
+
+fn join<'a>(values: impl Iterator<Item=&'a i64>) -> String {
+    return values
+        .map(|v| v.to_string())
+        .collect::<Vec<_>>()
+        .join(",");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn memory_past_the_last_address() {
+        let last = parse_snapshot("intcode-snapshot 1\nmemory 18446744073709551615 7\n").unwrap();
+        assert_eq!(last.memory.read(u64::MAX), 7);
+
+        let err = parse_snapshot("intcode-snapshot 1\nip 0\nmemory 18446744073709551615 7,8\n")
+            .unwrap_err();
+        assert_eq!(err.to_string(), "Line 3: memory runs past the last address");
+    }
+}
@@ -0,0 +1,757 @@
+#![allow(clippy::needless_return)]
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, VecDeque},
+    ops::RangeInclusive,
+    rc::Rc,
+};
+
+use anyhow::{Context, Result, anyhow, bail};
+
+use super::{Computer, Memory, Opcode, ParameterMode};
+
+// Paths explored before giving up on the rest.
+const MAX_PATHS: usize = 1000;
+// Instructions executed on a single path.
+const MAX_STEPS: u64 = 1_000_000;
+// Terms in a single expression, multiplying symbols can blow up quickly.
+const MAX_TERMS: usize = 1000;
+// Assignments the solver tries before giving up.
+const SOLVE_BUDGET: u64 = 10_000_000;
+
+// Polynomial over atoms with wrapping i64 coefficients, which keeps sums
+// and products of symbols in a canonical form. Each monomial is a sorted
+// list of atom ids and only non-zero coefficients are stored.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub(crate) struct Poly {
+    terms: BTreeMap<Vec<usize>, i64>,
+}
+
+impl Poly {
+    pub(crate) fn constant(val: i64) -> Self {
+        let mut poly = Poly::default();
+        if val != 0 {
+            poly.terms.insert(vec![], val);
+        }
+        return poly;
+    }
+
+    fn atom(id: usize) -> Self {
+        return Poly { terms: BTreeMap::from([(vec![id], 1)]) };
+    }
+
+    pub(crate) fn as_constant(&self) -> Option<i64> {
+        return match self.terms.iter().next() {
+            None => Some(0),
+            Some((monomial, &coef)) if monomial.is_empty() && self.terms.len() == 1 => Some(coef),
+            _ => None,
+        };
+    }
+
+    fn add(&self, other: &Poly) -> Poly {
+        let mut sum = self.clone();
+        for (monomial, &coef) in &other.terms {
+            sum.add_term(monomial.clone(), coef);
+        }
+        return sum;
+    }
+
+    fn sub(&self, other: &Poly) -> Poly {
+        return self.add(&other.mul(&Poly::constant(-1)));
+    }
+
+    fn mul(&self, other: &Poly) -> Poly {
+        let mut product = Poly::default();
+        for (m1, &c1) in &self.terms {
+            for (m2, &c2) in &other.terms {
+                let mut monomial = [m1.as_slice(), m2.as_slice()].concat();
+                monomial.sort();
+                product.add_term(monomial, c1.wrapping_mul(c2));
+            }
+        }
+        return product;
+    }
+
+    fn add_term(&mut self, monomial: Vec<usize>, coef: i64) {
+        let entry = self.terms.entry(monomial).or_default();
+        *entry = entry.wrapping_add(coef);
+        self.terms.retain(|_, coef| *coef != 0);
+    }
+
+    fn atoms(&self) -> impl Iterator<Item = usize> + '_ {
+        return self.terms.keys().flatten().copied();
+    }
+}
+
+// Memory cells written on a path, on top of the program's memory.
+type Cells = Rc<HashMap<u64, Poly>>;
+
+enum Atom {
+    Symbol(String),
+    LessThan(Poly, Poly),
+    Equals(Poly, Poly),
+    // Cell at an address that's only known symbolically, as memory was
+    // when it got read.
+    Load(Poly, Cells),
+}
+
+// A branch taken on a path: `poly` is non-zero, or zero.
+#[derive(Clone, Debug)]
+pub(crate) struct Condition {
+    pub(crate) poly: Poly,
+    pub(crate) nonzero: bool,
+}
+
+impl Condition {
+    pub(crate) fn equals(poly: &Poly, val: i64) -> Self {
+        return Condition { poly: poly.sub(&Poly::constant(val)), nonzero: false };
+    }
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct Output {
+    pub(crate) value: Poly,
+    // How many of the path's conditions it took to get here.
+    pub(crate) conditions: usize,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) enum End {
+    Halted,
+    Stopped {
+        ip: u64,
+        reason: String,
+    },
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct Path {
+    pub(crate) end: End,
+    pub(crate) conditions: Vec<Condition>,
+    pub(crate) outputs: Vec<Output>,
+    cells: Cells,
+}
+
+#[derive(Clone)]
+struct State {
+    ip: u64,
+    rel_base: i64,
+    cells: Cells,
+    inputs: usize,
+    conditions: Vec<Condition>,
+    outputs: Vec<Output>,
+    steps: u64,
+}
+
+// Runs a program on symbols instead of values: every input is a new symbol
+// `in0`, `in1`, ..., and so is every memory cell passed to `symbolize`.
+// Memory and outputs hold expressions over them, and execution forks at
+// every jump whose condition depends on them. Arithmetic wraps, since
+// overflows can't be told apart from valid results without values.
+pub(crate) struct Symbolic {
+    memory: Memory,
+    ip: u64,
+    rel_base: i64,
+    cells: HashMap<u64, Poly>,
+    atoms: Vec<Atom>,
+}
+
+impl Computer {
+    pub(crate) fn symbolic(&self) -> Symbolic {
+        return Symbolic {
+            memory: self.memory.clone(),
+            ip: self.ip,
+            rel_base: self.rel_base,
+            cells: HashMap::new(),
+            atoms: Vec::new(),
+        };
+    }
+}
+
+impl Symbolic {
+    pub(crate) fn symbolize(&mut self, addr: u64, name: &str) {
+        let symbol = self.intern(Atom::Symbol(name.to_string()));
+        self.cells.insert(addr, Poly::atom(symbol));
+    }
+
+    fn intern(&mut self, atom: Atom) -> usize {
+        let existing = self.atoms.iter().position(|a| match (a, &atom) {
+            (Atom::Symbol(a), Atom::Symbol(b)) => a == b,
+            (Atom::LessThan(a1, b1), Atom::LessThan(a2, b2)) => a1 == a2 && b1 == b2,
+            (Atom::Equals(a1, b1), Atom::Equals(a2, b2)) => a1 == a2 && b1 == b2,
+            _ => false,
+        });
+
+        return existing.unwrap_or_else(|| {
+            self.atoms.push(atom);
+            self.atoms.len() - 1
+        });
+    }
+
+    fn cell(&self, cells: &Cells, addr: u64) -> Poly {
+        return cells.get(&addr)
+            .cloned()
+            .unwrap_or_else(|| Poly::constant(self.memory.read(addr)));
+    }
+
+    // The cell at `addr` at the end of `path`.
+    pub(crate) fn read(&self, path: &Path, addr: u64) -> Poly {
+        return self.cell(&path.cells, addr);
+    }
+
+    // Every path through the program, breadth first so the ones with the
+    // fewest branches come first.
+    pub(crate) fn explore(&mut self) -> Vec<Path> {
+        let start = State {
+            ip: self.ip,
+            rel_base: self.rel_base,
+            cells: Rc::new(self.cells.clone()),
+            inputs: 0,
+            conditions: Vec::new(),
+            outputs: Vec::new(),
+            steps: 0,
+        };
+
+        let mut paths = Vec::new();
+        let mut pending = VecDeque::from([start]);
+        while let Some(state) = pending.pop_front() {
+            if paths.len() >= MAX_PATHS {
+                paths.push(stopped(state, "Too many paths"));
+                continue;
+            }
+            paths.push(self.run_path(state, &mut pending));
+        }
+
+        return paths;
+    }
+
+    fn run_path(&mut self, mut state: State, pending: &mut VecDeque<State>) -> Path {
+        loop {
+            if state.steps == MAX_STEPS {
+                return stopped(state, "Step budget exhausted");
+            }
+            state.steps += 1;
+
+            match self.step(&mut state, pending) {
+                Ok(false) => {},
+                Ok(true) => {
+                    return Path {
+                        end: End::Halted,
+                        conditions: state.conditions,
+                        outputs: state.outputs,
+                        cells: state.cells,
+                    };
+                },
+                Err(reason) => return stopped(state, &reason),
+            }
+        }
+    }
+
+    // Runs one instruction, returns whether the program halted. Forks go
+    // to `pending`.
+    fn step(&mut self, state: &mut State, pending: &mut VecDeque<State>)
+            -> std::result::Result<bool, String> {
+        let ip = state.ip;
+        let word = self.cell(&state.cells, ip).as_constant()
+            .ok_or("Instruction depends on symbols")?;
+        let opcode = Opcode::try_from(word % 100)
+            .map_err(|_| format!("Invalid opcode {word}"))?;
+
+        let mut modes = word / 100;
+        let mut params = Vec::new();
+        for idx in 0..opcode.arity() {
+            let mode = ParameterMode::try_from((modes % 10) as u8)
+                .map_err(|_| format!("Invalid mode in {word}"))?;
+            modes /= 10;
+            params.push((mode, self.cell(&state.cells, ip + 1 + idx)));
+        }
+
+        let next = ip + opcode.size();
+        match opcode {
+            Opcode::Add | Opcode::Mul | Opcode::LessThan | Opcode::Equals => {
+                let a = self.operand(state, &params[0])?;
+                let b = self.operand(state, &params[1])?;
+                let val = match opcode {
+                    Opcode::Add => a.add(&b),
+                    Opcode::Mul => a.mul(&b),
+                    Opcode::LessThan => match a.sub(&b).as_constant() {
+                        Some(diff) => Poly::constant((diff < 0) as i64),
+                        None => Poly::atom(self.intern(Atom::LessThan(a, b))),
+                    },
+                    _ => match a.sub(&b).as_constant() {
+                        Some(diff) => Poly::constant((diff == 0) as i64),
+                        None => Poly::atom(self.intern(Atom::Equals(a, b))),
+                    },
+                };
+                if val.terms.len() > MAX_TERMS {
+                    return Err(String::from("Expression too large"));
+                }
+
+                let dst = self.target(state, &params[2])?;
+                Rc::make_mut(&mut state.cells).insert(dst, val);
+            },
+            Opcode::Input => {
+                let symbol = self.intern(Atom::Symbol(format!("in{}", state.inputs)));
+                state.inputs += 1;
+
+                let dst = self.target(state, &params[0])?;
+                Rc::make_mut(&mut state.cells).insert(dst, Poly::atom(symbol));
+            },
+            Opcode::Output => {
+                let value = self.operand(state, &params[0])?;
+                state.outputs.push(Output { value, conditions: state.conditions.len() });
+            },
+            Opcode::JumpIfTrue | Opcode::JumpIfFalse => {
+                let cond = self.operand(state, &params[0])?;
+                let target = self.operand(state, &params[1])?.as_constant()
+                    .ok_or("Jump target depends on symbols")?;
+                if target < 0 {
+                    return Err(format!("Jump to negative address {target}"));
+                }
+
+                let jump_if = opcode == Opcode::JumpIfTrue;
+                let known = match cond.as_constant() {
+                    Some(val) => Some(val != 0),
+                    None => state.conditions.iter()
+                        .find(|c| c.poly == cond)
+                        .map(|c| c.nonzero),
+                };
+
+                let taken = match known {
+                    Some(nonzero) => nonzero == jump_if,
+                    None => {
+                        let mut other = state.clone();
+                        other.conditions.push(Condition { poly: cond.clone(), nonzero: !jump_if });
+                        other.ip = next;
+                        pending.push_back(other);
+
+                        state.conditions.push(Condition { poly: cond, nonzero: jump_if });
+                        true
+                    },
+                };
+                state.ip = if taken { target as u64 } else { next };
+                return Ok(false);
+            },
+            Opcode::AdjRelBase => {
+                let operand = self.operand(state, &params[0])?.as_constant()
+                    .ok_or("`rel_base` adjustment depends on symbols")?;
+                state.rel_base = state.rel_base.checked_add(operand)
+                    .ok_or_else(|| format!("`rel_base` of {} adjusted by {operand} overflows",
+                        state.rel_base))?;
+            },
+            Opcode::Halt => return Ok(true),
+            Opcode::Custom(_) => {
+                return Err(format!("`{}` can't run symbolically", opcode.mnemonic()));
+            },
+        }
+
+        state.ip = next;
+        return Ok(false);
+    }
+
+    fn address(&self, state: &State, (mode, raw): &(ParameterMode, Poly)) -> Poly {
+        return match mode {
+            ParameterMode::Relative => raw.add(&Poly::constant(state.rel_base)),
+            _ => raw.clone(),
+        };
+    }
+
+    fn operand(&mut self, state: &State, param: &(ParameterMode, Poly))
+            -> std::result::Result<Poly, String> {
+        if param.0 == ParameterMode::Immediate {
+            return Ok(param.1.clone());
+        }
+
+        let addr = self.address(state, param);
+        return match addr.as_constant() {
+            Some(addr) if addr < 0 => Err(format!("Read from negative address {addr}")),
+            Some(addr) => Ok(self.cell(&state.cells, addr as u64)),
+            None => Ok(Poly::atom(self.intern(Atom::Load(addr, state.cells.clone())))),
+        };
+    }
+
+    fn target(&self, state: &State, param: &(ParameterMode, Poly))
+            -> std::result::Result<u64, String> {
+        if param.0 == ParameterMode::Immediate {
+            return Err(String::from("Write to an immediate parameter"));
+        }
+
+        return match self.address(state, param).as_constant() {
+            Some(addr) if addr < 0 => Err(format!("Write to negative address {addr}")),
+            Some(addr) => Ok(addr as u64),
+            None => Err(String::from("Write to an address that depends on symbols")),
+        };
+    }
+
+    pub(crate) fn show(&self, poly: &Poly) -> String {
+        let mut terms = Vec::new();
+        for (monomial, &coef) in poly.terms.iter().filter(|(m, _)| !m.is_empty()) {
+            let factors = monomial.iter()
+                .map(|&atom| self.show_atom(atom))
+                .collect::<Vec<_>>()
+                .join("*");
+            terms.push(match coef {
+                1 => factors,
+                -1 => format!("-{factors}"),
+                _ => format!("{coef}*{factors}"),
+            });
+        }
+        if let Some(&constant) = poly.terms.get(&vec![]) {
+            terms.push(constant.to_string());
+        }
+
+        if terms.is_empty() {
+            return String::from("0");
+        }
+        return terms.join(" + ").replace("+ -", "- ");
+    }
+
+    fn show_atom(&self, atom: usize) -> String {
+        return match &self.atoms[atom] {
+            Atom::Symbol(name) => name.clone(),
+            Atom::LessThan(a, b) => format!("({} < {})", self.show(a), self.show(b)),
+            Atom::Equals(a, b) => format!("({} == {})", self.show(a), self.show(b)),
+            Atom::Load(addr, _) => format!("mem[{}]", self.show(addr)),
+        };
+    }
+
+    pub(crate) fn show_condition(&self, condition: &Condition) -> String {
+        let op = if condition.nonzero { "!=" } else { "==" };
+        return format!("{} {op} 0", self.show(&condition.poly));
+    }
+
+    // Values in `range` for the symbolized cells and every symbol in
+    // `conditions` that satisfy all of them, found by trying assignments.
+    // When only one symbol is left and a condition is a linear equation in
+    // it, its value is computed instead.
+    pub(crate) fn solve(&self, conditions: &[Condition], range: RangeInclusive<i64>)
+            -> Option<Vec<(String, i64)>> {
+        let mut symbols = BTreeSet::new();
+        for poly in conditions.iter().map(|c| &c.poly).chain(self.cells.values()) {
+            self.collect_symbols(poly, &mut symbols);
+        }
+        let symbols = symbols.into_iter().collect::<Vec<_>>();
+
+        let mut assignment = HashMap::new();
+        let mut budget = SOLVE_BUDGET;
+        if !self.search(conditions, &symbols, &range, &mut assignment, &mut budget) {
+            return None;
+        }
+
+        let solution = symbols.iter()
+            .map(|&s| (self.show_atom(s), assignment[&s]))
+            .collect();
+        return Some(solution);
+    }
+
+    fn collect_symbols(&self, poly: &Poly, symbols: &mut BTreeSet<usize>) {
+        for atom in poly.atoms() {
+            match &self.atoms[atom] {
+                Atom::Symbol(_) => {
+                    symbols.insert(atom);
+                },
+                Atom::LessThan(a, b) | Atom::Equals(a, b) => {
+                    self.collect_symbols(a, symbols);
+                    self.collect_symbols(b, symbols);
+                },
+                Atom::Load(addr, cells) => {
+                    self.collect_symbols(addr, symbols);
+                    for poly in cells.values() {
+                        self.collect_symbols(poly, symbols);
+                    }
+                },
+            }
+        }
+    }
+
+    fn search(
+        &self,
+        conditions: &[Condition],
+        symbols: &[usize],
+        range: &RangeInclusive<i64>,
+        assignment: &mut HashMap<usize, i64>,
+        budget: &mut u64,
+    ) -> bool {
+        // Conditions that can be decided already have to hold, and all of
+        // them can be decided once every symbol has a value.
+        for c in conditions {
+            match self.eval(&c.poly, assignment) {
+                Some(val) if (val != 0) != c.nonzero => return false,
+                None if symbols.is_empty() => return false,
+                _ => {},
+            }
+        }
+        let Some((&symbol, rest)) = symbols.split_first() else {
+            return true;
+        };
+
+        let candidates = match rest.is_empty() {
+            true => self.solve_linear(conditions, symbol, assignment),
+            false => None,
+        };
+        let candidates = match candidates {
+            Some(Some(val)) if range.contains(&val) => val..=val,
+            // An equation without a solution in range.
+            Some(_) => return false,
+            None => range.clone(),
+        };
+
+        for val in candidates {
+            if *budget == 0 {
+                break;
+            }
+            *budget -= 1;
+
+            assignment.insert(symbol, val);
+            if self.search(conditions, rest, range, assignment, budget) {
+                return true;
+            }
+        }
+        assignment.remove(&symbol);
+
+        return false;
+    }
+
+    // Value of `symbol` given by a condition `a * symbol + b == 0`, `None`
+    // if there's no such condition and `Some(None)` if it has no integer
+    // solution.
+    fn solve_linear(
+        &self,
+        conditions: &[Condition],
+        symbol: usize,
+        assignment: &HashMap<usize, i64>,
+    ) -> Option<Option<i64>> {
+        let mut assignment = assignment.clone();
+        for c in conditions.iter().filter(|c| !c.nonzero && self.is_linear_in(&c.poly, symbol)) {
+            assignment.insert(symbol, 0);
+            let Some(b) = self.eval(&c.poly, &assignment) else {
+                continue;
+            };
+            assignment.insert(symbol, 1);
+            let Some(a) = self.eval(&c.poly, &assignment).map(|v| v.wrapping_sub(b)) else {
+                continue;
+            };
+
+            if a != 0 {
+                // Only `i64::MIN / -1` fails, and its result doesn't fit.
+                let val = b.wrapping_neg();
+                let solution = match val.checked_rem(a) {
+                    Some(0) => val.checked_div(a),
+                    _ => None,
+                };
+                return Some(solution);
+            }
+        }
+
+        return None;
+    }
+
+    fn is_linear_in(&self, poly: &Poly, symbol: usize) -> bool {
+        return poly.terms.keys().all(|monomial| {
+            let count = monomial.iter().filter(|&&a| a == symbol).count();
+            let nested = monomial.iter().any(|&a| {
+                let mut symbols = BTreeSet::new();
+                if !matches!(self.atoms[a], Atom::Symbol(_)) {
+                    self.collect_symbols(&Poly::atom(a), &mut symbols);
+                }
+                symbols.contains(&symbol)
+            });
+            count <= 1 && !nested
+        });
+    }
+
+    fn eval(&self, poly: &Poly, assignment: &HashMap<usize, i64>) -> Option<i64> {
+        let mut sum = 0i64;
+        for (monomial, &coef) in &poly.terms {
+            let mut product = coef;
+            for &atom in monomial {
+                product = product.wrapping_mul(self.eval_atom(atom, assignment)?);
+            }
+            sum = sum.wrapping_add(product);
+        }
+        return Some(sum);
+    }
+
+    fn eval_atom(&self, atom: usize, assignment: &HashMap<usize, i64>) -> Option<i64> {
+        return match &self.atoms[atom] {
+            Atom::Symbol(_) => assignment.get(&atom).copied(),
+            Atom::LessThan(a, b) => Some((self.eval(a, assignment)? < self.eval(b, assignment)?) as i64),
+            Atom::Equals(a, b) => Some((self.eval(a, assignment)? == self.eval(b, assignment)?) as i64),
+            Atom::Load(addr, cells) => {
+                let addr = u64::try_from(self.eval(addr, assignment)?).ok()?;
+                match cells.get(&addr) {
+                    Some(poly) => self.eval(poly, assignment),
+                    None => Some(self.memory.read(addr)),
+                }
+            },
+        };
+    }
+}
+
+fn stopped(state: State, reason: &str) -> Path {
+    return Path {
+        end: End::Stopped { ip: state.ip, reason: reason.to_string() },
+        conditions: state.conditions,
+        outputs: state.outputs,
+        cells: state.cells,
+    };
+}
+
+fn parse_assignment(text: &str) -> Result<(u64, &str)> {
+    let (addr, rest) = text.split_once("=")
+        .ok_or_else(|| anyhow!("Expected `<addr>=<value>`, got `{text}`"))?;
+    let addr = addr.parse::<u64>().with_context(|| format!("Invalid address `{addr}`"))?;
+    return Ok((addr, rest));
+}
+
+fn parse_range(text: &str) -> Result<RangeInclusive<i64>> {
+    let (lo, hi) = text.split_once("..")
+        .ok_or_else(|| anyhow!("Expected a range like `0..99`, got `{text}`"))?;
+    let lo = lo.parse::<i64>().context("Invalid start of the range")?;
+    let hi = hi.parse::<i64>().context("Invalid end of the range")?;
+
+    return Ok(lo..=hi);
+}
+
+fn show_solution(solution: &[(String, i64)]) -> String {
+    return solution.iter()
+        .map(|(name, val)| format!("{name}={val}"))
+        .collect::<Vec<_>>()
+        .join(", ");
+}
+
+pub(crate) fn command(args: &[String]) -> Result<()> {
+    let usage = "Usage: symbolic <input_file> [--sym <addr>=<name>]... [--show <addr>]... \
+        [--mem <addr>=<value>] [--output <value>] [--range <lo>..<hi>]";
+
+    let mut positional = Vec::new();
+    let mut symbols = Vec::new();
+    let mut shown = Vec::new();
+    let mut mem_goal = None;
+    let mut output_goal = None;
+    let mut range = 0..=99;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| anyhow!("Missing value for `{arg}`"));
+        match arg.as_str() {
+            "--sym" => {
+                let (addr, name) = parse_assignment(value()?)?;
+                symbols.push((addr, name.to_string()));
+            },
+            "--show" => shown.push(value()?.parse::<u64>().context("Invalid address")?),
+            "--mem" => {
+                let (addr, val) = parse_assignment(value()?)?;
+                mem_goal = Some((addr, val.parse::<i64>().context("Invalid value")?));
+            },
+            "--output" => output_goal = Some(value()?.parse::<i64>().context("Invalid value")?),
+            "--range" => range = parse_range(value()?)?,
+            _ => positional.push(arg.clone()),
+        }
+    }
+
+    if positional.is_empty() {
+        bail!(usage);
+    }
+
+    let computer = super::asm::load(&positional[0])?;
+    let mut symbolic = computer.symbolic();
+    for (addr, name) in &symbols {
+        symbolic.symbolize(*addr, name);
+    }
+
+    let paths = symbolic.explore();
+    for (idx, path) in paths.iter().enumerate() {
+        match &path.end {
+            End::Halted => println!("Path {idx}: halted"),
+            End::Stopped { ip, reason } => println!("Path {idx}: stopped at {ip}: {reason}"),
+        }
+        for c in &path.conditions {
+            println!("  if {}", symbolic.show_condition(c));
+        }
+        for output in &path.outputs {
+            println!("  output {}", symbolic.show(&output.value));
+        }
+        for &addr in &shown {
+            println!("  mem[{addr}] = {}", symbolic.show(&symbolic.read(path, addr)));
+        }
+    }
+
+    if let Some((addr, val)) = mem_goal {
+        let solution = paths.iter()
+            .filter(|path| path.end == End::Halted)
+            .find_map(|path| {
+                let mut conditions = path.conditions.clone();
+                conditions.push(Condition::equals(&symbolic.read(path, addr), val));
+                symbolic.solve(&conditions, range.clone())
+            });
+        match solution {
+            Some(solution) => println!("mem[{addr}] = {val} with {}", show_solution(&solution)),
+            None => println!("No way found to halt with mem[{addr}] = {val}"),
+        }
+    }
+
+    if let Some(val) = output_goal {
+        let solution = paths.iter()
+            .flat_map(|path| path.outputs.iter().map(move |output| (path, output)))
+            .find_map(|(path, output)| {
+                let mut conditions = path.conditions[..output.conditions].to_vec();
+                conditions.push(Condition::equals(&output.value, val));
+                symbolic.solve(&conditions, range.clone())
+            });
+        match solution {
+            Some(solution) => println!("Output {val} with {}", show_solution(&solution)),
+            None => println!("No way found to output {val}"),
+        }
+    }
+
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::computer::asm::assemble_computer;
+
+    // `mem[0] = -n + c`, solved for `mem[0] == 0`.
+    fn solve_negated(c: i64) -> Option<Vec<(String, i64)>> {
+        let computer = assemble_computer(&format!("
+                    MUL [n], #-1, [n]
+                    ADD [n], #{c}, [0]
+                    HLT
+            n:      .data 0
+        ")).unwrap();
+        let mut symbolic = computer.symbolic();
+        symbolic.symbolize(9, "n");
+
+        let paths = symbolic.explore();
+        assert_eq!(paths.len(), 1);
+        assert_eq!(paths[0].end, End::Halted);
+
+        let result = symbolic.read(&paths[0], 0);
+        return symbolic.solve(&[Condition::equals(&result, 0)], 0..=99);
+    }
+
+    #[test]
+    fn linear_solutions() {
+        assert_eq!(solve_negated(42), Some(vec![(String::from("n"), 42)]));
+        assert_eq!(solve_negated(-1), None);
+        // Solving would divide `i64::MIN` by -1.
+        assert_eq!(solve_negated(i64::MIN), None);
+    }
+
+    #[test]
+    fn rel_base_overflow_stops_the_path() {
+        let computer = assemble_computer("
+                    ARB #9223372036854775807
+                    ARB #1
+                    HLT
+        ").unwrap();
+        let paths = computer.symbolic().explore();
+        assert_eq!(paths.len(), 1);
+        assert_eq!(paths[0].end, End::Stopped {
+            ip: 2,
+            reason: String::from("`rel_base` of 9223372036854775807 adjusted by 1 overflows"),
+        });
+    }
+}
@@ -0,0 +1,46 @@
+#![allow(clippy::needless_return)]
+
+use std::{sync::mpsc::{Receiver, Sender}, thread::{self, JoinHandle}};
+
+use super::{Computer, ComputerError, IntcodeIo, Status};
+
+// Connects a VM to channels. Reading blocks until a value arrives, and the
+// VM only pauses for input once every sender is gone.
+pub(crate) struct ChannelIo {
+    input: Receiver<i64>,
+    output: Sender<i64>,
+}
+
+impl ChannelIo {
+    pub(crate) fn new(input: Receiver<i64>, output: Sender<i64>) -> Self {
+        return ChannelIo { input, output };
+    }
+}
+
+impl IntcodeIo for ChannelIo {
+    fn read(&mut self) -> Option<i64> {
+        return self.input.recv().ok();
+    }
+
+    fn write(&mut self, val: i64) {
+        // Nobody listening anymore, e.g. the next VM in a loop already
+        // halted. The program doesn't care, so neither do we.
+        let _ = self.output.send(val);
+    }
+}
+
+pub(crate) type ComputerThread = JoinHandle<std::result::Result<(Computer, Status), ComputerError>>;
+
+impl Computer {
+    // Runs the program on its own thread until it halts, or stops for input
+    // after every sender of `input` was dropped. Joining gives the VM back
+    // along with how it stopped.
+    pub(crate) fn spawn(mut self, input: Receiver<i64>, output: Sender<i64>) -> ComputerThread {
+        return thread::spawn(move || {
+            let mut io = ChannelIo::new(input, output);
+            let status = self.run_io(&mut io)?;
+
+            return Ok((self, status));
+        });
+    }
+}
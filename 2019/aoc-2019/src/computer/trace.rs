@@ -0,0 +1,286 @@
+#![allow(clippy::needless_return)]
+
+use std::{fs::File, io::{self, BufWriter, Write}, ops::RangeInclusive, sync::{Arc, Mutex}};
+
+use anyhow::{Context, Result, anyhow, bail};
+
+use super::{
+    Computer, ComputerError, Event, Observer, Opcode, attach_global_observer, disasm,
+};
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum TraceFormat {
+    // One JSON object per executed instruction.
+    JsonLines,
+    // `ICTR` magic and a version byte, followed by one record per executed
+    // instruction, all integers little-endian:
+    //
+    //     ip: u64, opcode: u8, flags: u8, operand count: u8,
+    //     operands: i64 * count,
+    //     if flags & 1: write addr: u64, old: i64, new: i64
+    //     if flags & 2: new rel_base: i64
+    //     if flags & 4: consumed input: i64
+    //     if flags & 8: produced output: i64
+    Binary,
+}
+
+const BINARY_MAGIC: &[u8; 4] = b"ICTR";
+const BINARY_VERSION: u8 = 1;
+
+const FLAG_WRITE: u8 = 1;
+const FLAG_REL_BASE: u8 = 2;
+const FLAG_INPUT: u8 = 4;
+const FLAG_OUTPUT: u8 = 8;
+
+// Which executed instructions end up in the trace, everything by default.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct TraceFilter {
+    pub(crate) ip: Option<RangeInclusive<u64>>,
+    pub(crate) opcodes: Option<Vec<Opcode>>,
+}
+
+impl TraceFilter {
+    fn matches(&self, event: &Event) -> bool {
+        if let Some(range) = &self.ip && !range.contains(&event.ip) {
+            return false;
+        }
+
+        if let Some(opcodes) = &self.opcodes
+                && !opcodes.contains(&event.instruction.opcode) {
+            return false;
+        }
+
+        return true;
+    }
+}
+
+pub(crate) struct Tracer {
+    out: BufWriter<File>,
+    format: TraceFormat,
+    filter: TraceFilter,
+    step: u64,
+    // Text of the instruction about to run, taken before it executes since
+    // it may overwrite itself.
+    text: String,
+    error: Option<io::Error>,
+}
+
+impl Tracer {
+    pub(crate) fn create(path: &str, format: TraceFormat, filter: TraceFilter) -> Result<Self> {
+        let file = File::create(path)
+            .with_context(|| format!("Couldn't create trace file `{path}`"))?;
+        let mut out = BufWriter::new(file);
+
+        if format == TraceFormat::Binary {
+            out.write_all(BINARY_MAGIC)?;
+            out.write_all(&[BINARY_VERSION])?;
+        }
+
+        return Ok(Tracer {
+            out,
+            format,
+            filter,
+            step: 0,
+            text: String::new(),
+            error: None,
+        });
+    }
+
+    pub(crate) fn flush(&mut self) -> Result<()> {
+        if let Some(e) = self.error.take() {
+            return Err(e).context("Failed to write the trace");
+        }
+
+        self.out.flush().context("Failed to write the trace")?;
+        return Ok(());
+    }
+
+    fn write_event(&mut self, computer: &Computer, event: &Event) -> io::Result<()> {
+        let record = Record::new(computer, event);
+        return match self.format {
+            TraceFormat::JsonLines => self.write_json(event, &record),
+            TraceFormat::Binary => self.write_binary(event, &record),
+        };
+    }
+
+    fn write_json(&mut self, event: &Event, record: &Record) -> io::Result<()> {
+        let operands = event.instruction.operands().iter()
+            .map(|o| o.to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+
+        let mut line = format!(
+            "{{\"step\":{},\"ip\":{},\"op\":\"{}\",\"text\":\"{}\",\"operands\":[{operands}]",
+            self.step,
+            event.ip,
+            event.instruction.opcode.mnemonic(),
+            self.text,
+        );
+        if let Some(w) = event.write {
+            line += &format!(
+                ",\"write\":{{\"addr\":{},\"old\":{},\"new\":{}}}",
+                w.addr, w.old, w.new,
+            );
+        }
+        if let Some((old, new)) = record.rel_base {
+            line += &format!(",\"rel_base\":{{\"old\":{old},\"new\":{new}}}");
+        }
+        if let Some(val) = record.input {
+            line += &format!(",\"input\":{val}");
+        }
+        if let Some(val) = record.output {
+            line += &format!(",\"output\":{val}");
+        }
+        line += "}\n";
+
+        return self.out.write_all(line.as_bytes());
+    }
+
+    fn write_binary(&mut self, event: &Event, record: &Record) -> io::Result<()> {
+        let operands = event.instruction.operands();
+        let mut flags = 0;
+        if event.write.is_some() {
+            flags |= FLAG_WRITE;
+        }
+        if record.rel_base.is_some() {
+            flags |= FLAG_REL_BASE;
+        }
+        if record.input.is_some() {
+            flags |= FLAG_INPUT;
+        }
+        if record.output.is_some() {
+            flags |= FLAG_OUTPUT;
+        }
+
+        let mut buf = Vec::with_capacity(64);
+        buf.extend(event.ip.to_le_bytes());
+        buf.extend([event.instruction.opcode.code(), flags, operands.len() as u8]);
+        for o in operands {
+            buf.extend(o.to_le_bytes());
+        }
+        if let Some(w) = event.write {
+            buf.extend(w.addr.to_le_bytes());
+            buf.extend(w.old.to_le_bytes());
+            buf.extend(w.new.to_le_bytes());
+        }
+        if let Some((_, new)) = record.rel_base {
+            buf.extend(new.to_le_bytes());
+        }
+        if let Some(val) = record.input {
+            buf.extend(val.to_le_bytes());
+        }
+        if let Some(val) = record.output {
+            buf.extend(val.to_le_bytes());
+        }
+
+        return self.out.write_all(&buf);
+    }
+}
+
+// Parts of an event that are derived from the instruction.
+struct Record {
+    rel_base: Option<(i64, i64)>,
+    input: Option<i64>,
+    output: Option<i64>,
+}
+
+impl Record {
+    fn new(computer: &Computer, event: &Event) -> Self {
+        let operands = event.instruction.operands();
+        let rel_base = match event.instruction.opcode {
+            Opcode::AdjRelBase => {
+                Some((computer.rel_base - operands[0], computer.rel_base))
+            },
+            _ => None,
+        };
+        let input = match event.instruction.opcode {
+            Opcode::Input => event.write.map(|w| w.new),
+            _ => None,
+        };
+        let output = match event.instruction.opcode {
+            Opcode::Output => Some(operands[0]),
+            _ => None,
+        };
+
+        return Record { rel_base, input, output };
+    }
+}
+
+impl Observer for Tracer {
+    fn before_step(&mut self, computer: &mut Computer) -> std::result::Result<(), ComputerError> {
+        let in_range = self.filter.ip.as_ref()
+            .is_none_or(|range| range.contains(&computer.ip));
+        if self.format == TraceFormat::JsonLines && in_range {
+            let line = disasm::line_at(&computer.memory, computer.ip);
+            self.text = line.text();
+        }
+
+        return Ok(());
+    }
+
+    fn after_step(&mut self, computer: &Computer, event: &Event) {
+        if self.error.is_none()
+                && self.filter.matches(event)
+                && let Err(e) = self.write_event(computer, event) {
+            self.error = Some(e);
+        }
+
+        self.step += 1;
+    }
+}
+
+fn parse_filter_range(text: &str) -> Result<RangeInclusive<u64>> {
+    let (lo, hi) = text.split_once("..")
+        .ok_or_else(|| anyhow!("Expected an address range like `100..200`, got `{text}`"))?;
+    let lo = lo.parse::<u64>().context("Invalid start of the address range")?;
+    let hi = hi.parse::<u64>().context("Invalid end of the address range")?;
+
+    return Ok(lo..=hi);
+}
+
+fn parse_filter_opcodes(text: &str) -> Result<Vec<Opcode>> {
+    return text.split(",")
+        .map(|m| {
+            Opcode::from_mnemonic(m.trim())
+                .ok_or_else(|| anyhow!("Unknown mnemonic `{m}`"))
+        })
+        .collect();
+}
+
+pub(crate) fn command(args: &[String]) -> Result<()> {
+    let usage = "Usage: trace <out_file> <day> <input_file> [part] \
+        [--format json|bin] [--ip <lo>..<hi>] [--op <MNEMONIC,...>]";
+
+    let mut positional = Vec::new();
+    let mut format = TraceFormat::JsonLines;
+    let mut filter = TraceFilter::default();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| anyhow!("Missing value for `{arg}`"));
+        match arg.as_str() {
+            "--format" => {
+                format = match value()?.as_str() {
+                    "json" => TraceFormat::JsonLines,
+                    "bin" => TraceFormat::Binary,
+                    f => bail!("Unknown trace format `{f}`"),
+                };
+            },
+            "--ip" => filter.ip = Some(parse_filter_range(value()?)?),
+            "--op" => filter.opcodes = Some(parse_filter_opcodes(value()?)?),
+            _ => positional.push(arg.clone()),
+        }
+    }
+
+    if positional.len() < 3 {
+        bail!(usage);
+    }
+
+    let tracer = Arc::new(Mutex::new(Tracer::create(&positional[0], format, filter)?));
+    attach_global_observer(tracer.clone());
+
+    let res = crate::run_day_with_args(&positional[1..]);
+    tracer.lock().unwrap().flush()?;
+
+    return res;
+}
@@ -0,0 +1,449 @@
+#![allow(clippy::needless_return)]
+
+use std::fmt::Write;
+
+use anyhow::{Context, Result, bail};
+
+use super::{
+    Arithmetic, Computer, ComputerError, Decoded, IntcodeIo, Memory, Opcode, ParameterMode,
+    RunOutput, Status, cfg, disasm, io::IterIo,
+};
+
+// A program compiled ahead of time by `transpile`, which runs in place of
+// the interpreter for much less per instruction.
+//
+// Only the instruction words, i.e. opcodes and modes, are baked into the
+// generated code. Parameters are read from memory as they're used, so a
+// program whose parameters get patched before running, like day 2's, still
+// runs compiled. Anything the generated code can't handle goes back to the
+// interpreter: a VM that doesn't start out with the same instruction words,
+// one with instrumentation or non-default arithmetic, a jump to an address
+// that wasn't compiled, or a write over an instruction word.
+pub(crate) struct Transpiled {
+    // Address and value of the first word of every compiled instruction.
+    pub(crate) opcodes: &'static [(u64, i64)],
+    pub(crate) run_blocks: RunBlocks,
+}
+
+// Runs from `computer.ip`, counting executed instructions off the budget.
+// Returns `None` when the interpreter has to take over.
+pub(crate) type RunBlocks = fn(&mut Computer, &mut dyn IntcodeIo, &mut u64)
+    -> std::result::Result<Option<Status>, ComputerError>;
+
+impl Transpiled {
+    pub(crate) fn run(&self, computer: &mut Computer, inputs: impl Iterator<Item=i64>)
+            -> std::result::Result<RunOutput, ComputerError> {
+        return self.run_with_budget(computer, inputs, u64::MAX);
+    }
+
+    pub(crate) fn run_with_budget(
+        &self,
+        computer: &mut Computer,
+        inputs: impl Iterator<Item=i64>,
+        budget: u64,
+    ) -> std::result::Result<RunOutput, ComputerError> {
+        let mut io = IterIo::new(inputs);
+        let status = self.run_io_with_budget(computer, &mut io, budget)?;
+
+        return Ok(RunOutput { outputs: io.outputs, status });
+    }
+
+    pub(crate) fn run_io_with_budget(
+        &self,
+        computer: &mut Computer,
+        io: &mut impl IntcodeIo,
+        budget: u64,
+    ) -> std::result::Result<Status, ComputerError> {
+        if !self.can_run(computer) {
+            return computer.run_io_with_budget(io, budget);
+        }
+
+        let mut left = budget;
+        return match (self.run_blocks)(computer, io, &mut left)? {
+            Some(status) => Ok(status),
+            None => computer.run_io_with_budget(io, left),
+        };
+    }
+
+    fn can_run(&self, computer: &Computer) -> bool {
+        return computer.arithmetic == Arithmetic::Checked
+            && computer.history.is_none()
+            && computer.livelock.is_none()
+            && computer.observers.0.is_empty()
+            && !computer.memory.has_big_cells()
+            && self.opcodes.iter().all(|&(addr, word)| computer.memory.read(addr) == word);
+    }
+}
+
+// Rust source for a module exposing the program as `PROGRAM`, a
+// `Transpiled`. Every block of `cfg::build` becomes an arm of a match on
+// `ip`, and so does every `IN`, so a program paused for input resumes in
+// compiled code. Registered opcodes end their arm and hand over to the
+// interpreter.
+pub(crate) fn transpile(memory: &Memory, source: &str) -> String {
+    let cfg = cfg::build(memory);
+
+    let mut arms = Vec::new();
+    for block in cfg.blocks.values() {
+        let mut arm: Vec<(u64, Decoded)> = Vec::new();
+        for &(addr, decoded) in &block.instructions {
+            if decoded.opcode == Opcode::Input && !arm.is_empty() {
+                arms.push(std::mem::take(&mut arm));
+            }
+            arm.push((addr, decoded));
+            if leaves_arm(decoded.opcode) {
+                break;
+            }
+        }
+        if !arm.is_empty() {
+            arms.push(arm);
+        }
+    }
+
+    let opcodes = arms.iter()
+        .flatten()
+        .map(|&(addr, _)| (addr, memory.read(addr)))
+        .collect::<Vec<_>>();
+    let uses = |opcode| arms.iter().flatten().any(|(_, decoded)| decoded.opcode == opcode);
+    let uses_input = uses(Opcode::Input);
+    let uses_io = uses_input || uses(Opcode::Output);
+
+    let mut out = String::new();
+    writeln!(out, "// Generated by `aoc-2019 transpile {source}`, don't edit by hand.").unwrap();
+    writeln!(out, "//").unwrap();
+    writeln!(out, "// Declare this file as a module and run the program with").unwrap();
+    writeln!(out, "// `PROGRAM.run(&mut computer, inputs)` instead of `computer.run(inputs)`.").unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "#![allow(clippy::needless_return)]").unwrap();
+    writeln!(out).unwrap();
+    let mut imports = vec!["Computer", "ComputerError", "IntcodeIo"];
+    imports.extend(["Opcode", "Status", "checked_address"]);
+    if uses(Opcode::Add) || uses(Opcode::Mul) {
+        imports.push("checked_arithmetic");
+    }
+    if uses(Opcode::AdjRelBase) {
+        imports.push("checked_rel_base");
+    }
+    let relative = arms.iter()
+        .flatten()
+        .any(|(_, decoded)| decoded.params().iter().any(|p| p.mode == ParameterMode::Relative));
+    if relative {
+        imports.push("relative_address");
+    }
+    imports.push("transpile::Transpiled");
+    writeln!(out, "use crate::computer::{{{}}};", imports.join(", ")).unwrap();
+    writeln!(out).unwrap();
+
+    writeln!(out, "pub(crate) static PROGRAM: Transpiled = Transpiled {{").unwrap();
+    writeln!(out, "    opcodes: &[").unwrap();
+    for chunk in opcodes.chunks(8) {
+        let line = chunk.iter()
+            .map(|(addr, word)| format!("({addr}, {word})"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        writeln!(out, "        {line},").unwrap();
+    }
+    writeln!(out, "    ],").unwrap();
+    writeln!(out, "    run_blocks,").unwrap();
+    writeln!(out, "}};").unwrap();
+    writeln!(out).unwrap();
+
+    write_is_code(&mut out, &opcodes);
+
+    let mut fields = vec!["memory: m", "ip", "rel_base: rb"];
+    if uses_input {
+        fields.push("pending_input");
+    }
+    let io = if uses_io { "io" } else { "_io" };
+    // Jumps always end an arm, so only arms that halt or hand over to the
+    // interpreter never go around again.
+    let loops = arms.iter().any(|arm| !leaves_arm(arm[arm.len() - 1].1.opcode));
+    if !loops {
+        writeln!(out, "#[allow(clippy::never_loop)]").unwrap();
+    }
+    writeln!(out, "fn run_blocks(computer: &mut Computer, {io}: &mut dyn IntcodeIo, budget: &mut u64)").unwrap();
+    writeln!(out, "        -> Result<Option<Status>, ComputerError> {{").unwrap();
+    writeln!(out, "    let Computer {{ {}, .. }} = computer;", fields.join(", ")).unwrap();
+    writeln!(out, "    loop {{").unwrap();
+    writeln!(out, "        match *ip {{").unwrap();
+    for arm in &arms {
+        write_arm(&mut out, memory, arm);
+    }
+    writeln!(out, "            _ => return Ok(None),").unwrap();
+    writeln!(out, "        }}").unwrap();
+    writeln!(out, "    }}").unwrap();
+    writeln!(out, "}}").unwrap();
+
+    return out;
+}
+
+// Bitmap lookup of the compiled instruction words, checked after every write.
+fn write_is_code(out: &mut String, opcodes: &[(u64, i64)]) {
+    let len = opcodes.last().map_or(0, |(addr, _)| addr / 64 + 1);
+    let mut bits = vec![0u64; len as usize];
+    for (addr, _) in opcodes {
+        bits[(addr / 64) as usize] |= 1 << (addr % 64);
+    }
+
+    writeln!(out, "fn is_code(addr: u64) -> bool {{").unwrap();
+    writeln!(out, "    const CODE: [u64; {len}] = [").unwrap();
+    for chunk in bits.chunks(4) {
+        let line = chunk.iter()
+            .map(|b| format!("{b:#018x}"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        writeln!(out, "        {line},").unwrap();
+    }
+    writeln!(out, "    ];").unwrap();
+    writeln!(out, "    return addr < {} && (CODE[(addr / 64) as usize] >> (addr % 64)) & 1 != 0;", len * 64).unwrap();
+    writeln!(out, "}}").unwrap();
+    writeln!(out).unwrap();
+}
+
+fn write_arm(out: &mut String, memory: &Memory, arm: &[(u64, Decoded)]) {
+    let start = arm[0].0;
+    let (last, last_decoded) = arm[arm.len() - 1];
+    let len = arm.len();
+
+    writeln!(out, "            {start} => {{").unwrap();
+    writeln!(out, "                if *budget < {len} {{").unwrap();
+    writeln!(out, "                    return Ok(None);").unwrap();
+    writeln!(out, "                }}").unwrap();
+    writeln!(out, "                *budget -= {len};").unwrap();
+
+    for (idx, &(addr, decoded)) in arm.iter().enumerate() {
+        writeln!(out).unwrap();
+        writeln!(out, "                // {addr}: {}", disasm::line_at(memory, addr).text()).unwrap();
+        writeln!(out, "                *ip = {addr};").unwrap();
+        // Instructions of the arm after this one, given back to the budget
+        // when leaving early.
+        let rest = len - idx - 1;
+        for line in instruction(addr, &decoded, rest) {
+            writeln!(out, "                {line}").unwrap();
+        }
+    }
+
+    if !leaves_arm(last_decoded.opcode) {
+        writeln!(out, "                *ip = {};", last + last_decoded.size()).unwrap();
+    }
+    writeln!(out, "            }},").unwrap();
+}
+
+// Value of parameter `idx` of the instruction at `addr`.
+fn operand(addr: u64, decoded: &Decoded, idx: usize) -> String {
+    let cell = addr + 1 + idx as u64;
+    return match decoded.params[idx].mode {
+        ParameterMode::Immediate => format!("m.read({cell})"),
+        _ => format!("m.read({})", target(addr, decoded, idx)),
+    };
+}
+
+// Address parameter `idx` of the instruction at `addr` refers to.
+fn target(addr: u64, decoded: &Decoded, idx: usize) -> String {
+    let cell = addr + 1 + idx as u64;
+    let opcode = opcode_path(decoded.opcode);
+    return match decoded.params[idx].mode {
+        ParameterMode::Relative =>
+            format!("relative_address({addr}, *rb, {opcode}, m.read({cell}))?"),
+        _ => format!("checked_address({addr}, *rb, {opcode}, m.read({cell}))?"),
+    };
+}
+
+// How generated code names `opcode`, e.g. `Opcode::Add`.
+fn opcode_path(opcode: Opcode) -> String {
+    return format!("Opcode::{opcode:?}");
+}
+
+fn instruction(addr: u64, decoded: &Decoded, rest: usize) -> Vec<String> {
+    let next = addr + decoded.size();
+    let refund = match rest {
+        0 => String::new(),
+        n => format!("*budget += {n}; "),
+    };
+    let check_write = format!(
+        "if is_code(dst) {{ *ip = {next}; {refund}return Ok(None); }}",
+    );
+
+    return match decoded.opcode {
+        Opcode::Add | Opcode::Mul | Opcode::LessThan | Opcode::Equals => {
+            let value = match decoded.opcode {
+                Opcode::Add => format!("checked_arithmetic({addr}, *rb, Opcode::Add, a, b)?"),
+                Opcode::Mul => format!("checked_arithmetic({addr}, *rb, Opcode::Mul, a, b)?"),
+                Opcode::LessThan => String::from("(a < b) as i64"),
+                _ => String::from("(a == b) as i64"),
+            };
+            vec![
+                format!("let a = {};", operand(addr, decoded, 0)),
+                format!("let b = {};", operand(addr, decoded, 1)),
+                format!("let dst = {};", target(addr, decoded, 2)),
+                format!("m.write(dst, {value});"),
+                check_write,
+            ]
+        },
+        Opcode::Input => vec![
+            format!("let dst = {};", target(addr, decoded, 0)),
+            String::from("let Some(val) = pending_input.pop_front().or_else(|| io.read()) else {"),
+            // The `IN` runs again once there's input, so it's given back too.
+            format!("    *budget += {};", rest + 1),
+            String::from("    return Ok(Some(Status::PausedForInput));"),
+            String::from("};"),
+            String::from("m.write(dst, val);"),
+            check_write,
+        ],
+        Opcode::Output => vec![format!("io.write({});", operand(addr, decoded, 0))],
+        Opcode::JumpIfTrue | Opcode::JumpIfFalse => {
+            let cmp = match decoded.opcode {
+                Opcode::JumpIfTrue => "!=",
+                _ => "==",
+            };
+            vec![
+                format!("if {} {cmp} 0 {{", operand(addr, decoded, 0)),
+                format!(
+                    "    *ip = checked_address({addr}, *rb, {}, {})?;",
+                    opcode_path(decoded.opcode),
+                    operand(addr, decoded, 1),
+                ),
+                format!("    {refund}continue;"),
+                String::from("}"),
+            ]
+        },
+        Opcode::AdjRelBase => vec![
+            format!("*rb = checked_rel_base({addr}, *rb, {})?;", operand(addr, decoded, 0)),
+        ],
+        Opcode::Halt => vec![String::from("return Ok(Some(Status::Halted));")],
+        Opcode::Custom(_) => vec![
+            String::from("*budget += 1;"),
+            String::from("return Ok(None);"),
+        ],
+    };
+}
+
+// Whether nothing after the instruction runs compiled.
+fn leaves_arm(opcode: Opcode) -> bool {
+    return matches!(opcode, Opcode::Halt | Opcode::Custom(_));
+}
+
+pub(crate) fn command(args: &[String]) -> Result<()> {
+    if args.is_empty() {
+        bail!("Usage: transpile <input_file> [out_file]");
+    }
+
+    let computer = super::asm::load(&args[0])?;
+    let source = transpile(&computer.memory, &args[0]);
+    match args.get(1) {
+        Some(path) => std::fs::write(path, source)
+            .with_context(|| format!("Couldn't write `{path}`"))?,
+        None => print!("{source}"),
+    }
+
+    return Ok(());
+}
+
+#[cfg(test)]
+mod sample;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::computer::asm::assemble_computer;
+
+    const SAMPLE_FILE: &str = "src/computer/transpile/sample.asm";
+
+    fn sample() -> Computer {
+        return assemble_computer(include_str!("transpile/sample.asm")).unwrap();
+    }
+
+    #[test]
+    fn sample_is_up_to_date() {
+        assert_eq!(transpile(&sample().memory, SAMPLE_FILE), include_str!("transpile/sample.rs"));
+    }
+
+    // Outputs, status, `ip` and `rel_base` after a run.
+    type Run = (Vec<i64>, Status, u64, i64);
+
+    // Every run of the sample, `budget` instructions at a time, with `count`
+    // given once it pauses for input. Ends with the memory it halted with.
+    fn run_in_slices(compiled: bool, count: i64, budget: u64) -> (Vec<Run>, Vec<(u64, i64)>) {
+        let mut computer = sample();
+        let mut runs = Vec::new();
+        let mut inputs = Vec::new();
+        while runs.len() < 1000 {
+            let output = match compiled {
+                true => sample::PROGRAM.run_with_budget(&mut computer, inputs.drain(..), budget),
+                false => computer.run_with_budget(inputs.drain(..), budget),
+            };
+            let output = output.unwrap();
+            let status = output.status;
+            if status == Status::PausedForInput {
+                inputs.push(count);
+            }
+
+            let halted = status == Status::Halted;
+            runs.push((output.outputs, status, computer.ip, computer.rel_base));
+            if halted {
+                break;
+            }
+        }
+
+        return (runs, computer.memory.cells());
+    }
+
+    #[test]
+    fn sample_runs_like_the_interpreter() {
+        assert!(sample::PROGRAM.can_run(&sample()));
+
+        let (runs, _) = run_in_slices(true, 3, u64::MAX);
+        let outputs = runs.into_iter().flat_map(|(outputs, ..)| outputs).collect::<Vec<_>>();
+        assert_eq!(outputs, vec![3, 9, 2, 6, 1, 3, 3]);
+
+        for count in [1, 3] {
+            for budget in (1..=40).chain([u64::MAX]) {
+                assert_eq!(
+                    run_in_slices(true, count, budget),
+                    run_in_slices(false, count, budget),
+                    "count {count}, budget {budget}",
+                );
+            }
+        }
+    }
+
+    // Fault the sample runs into with `rel_base` starting out at `rel_base`
+    // and the `OUT rb+0` offset patched to `offset`.
+    fn overflow(compiled: bool, rel_base: i64, offset: i64) -> (ComputerError, u64) {
+        let mut computer = sample();
+        computer.rel_base = rel_base;
+        computer.memory.write(24, offset);
+        let output = match compiled {
+            true => sample::PROGRAM.run(&mut computer, [1].into_iter()),
+            false => computer.run([1].into_iter()),
+        };
+
+        return (output.unwrap_err(), computer.ip);
+    }
+
+    #[test]
+    fn sample_overflows_like_the_interpreter() {
+        let (err, ip) = overflow(true, i64::MAX, 0);
+        assert_eq!(err, ComputerError::Overflow {
+            ip: 21,
+            rel_base: i64::MAX,
+            opcode: Opcode::AdjRelBase,
+            a: i64::MAX,
+            b: 27,
+        });
+        assert_eq!(ip, 21);
+
+        let (err, ip) = overflow(true, 0, i64::MAX);
+        assert_eq!(err, ComputerError::AddressOverflow {
+            ip: 23,
+            rel_base: 27,
+            opcode: Opcode::Output,
+            offset: i64::MAX,
+        });
+        assert_eq!(ip, 23);
+
+        for (rel_base, offset) in [(i64::MAX, 0), (0, i64::MAX), (-28, 0)] {
+            assert_eq!(overflow(true, rel_base, offset), overflow(false, rel_base, offset));
+        }
+    }
+}
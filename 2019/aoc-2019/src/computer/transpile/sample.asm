@@ -0,0 +1,17 @@
+; Test program for the transpiler, compiled into `sample.rs` with
+; `aoc-2019 transpile src/computer/transpile/sample.asm src/computer/transpile/sample.rs`.
+; Pauses for a count, outputs it and three times it while counting down,
+; then writes over an instruction word, which hands over to the interpreter.
+
+start:  IN [n]
+loop:   OUT [n]
+        MUL [n], #3, [t]
+        OUT [t]
+        ADD [n], #-1, [n]
+        JT [n], #loop
+        ADD #0, [loop], [loop]  ; same value, but still a write into code
+        ARB #t
+        OUT rb+0
+        HLT
+n:      .data 0
+t:      .data 0
@@ -0,0 +1,114 @@
+// Generated by `aoc-2019 transpile src/computer/transpile/sample.asm`, don't edit by hand.
+//
+// Declare this file as a module and run the program with
+// `PROGRAM.run(&mut computer, inputs)` instead of `computer.run(inputs)`.
+
+#![allow(clippy::needless_return)]
+
+use crate::computer::{Computer, ComputerError, IntcodeIo, Opcode, Status, checked_address, checked_arithmetic, checked_rel_base, relative_address, transpile::Transpiled};
+
+pub(crate) static PROGRAM: Transpiled = Transpiled {
+    opcodes: &[
+        (0, 3), (2, 4), (4, 1002), (8, 4), (10, 1001), (14, 1005), (17, 101), (21, 109),
+        (23, 204), (25, 99),
+    ],
+    run_blocks,
+};
+
+fn is_code(addr: u64) -> bool {
+    const CODE: [u64; 1] = [
+        0x0000000002a24515,
+    ];
+    return addr < 64 && (CODE[(addr / 64) as usize] >> (addr % 64)) & 1 != 0;
+}
+
+fn run_blocks(computer: &mut Computer, io: &mut dyn IntcodeIo, budget: &mut u64)
+        -> Result<Option<Status>, ComputerError> {
+    let Computer { memory: m, ip, rel_base: rb, pending_input, .. } = computer;
+    loop {
+        match *ip {
+            0 => {
+                if *budget < 1 {
+                    return Ok(None);
+                }
+                *budget -= 1;
+
+                // 0: IN [26]
+                *ip = 0;
+                let dst = checked_address(0, *rb, Opcode::Input, m.read(1))?;
+                let Some(val) = pending_input.pop_front().or_else(|| io.read()) else {
+                    *budget += 1;
+                    return Ok(Some(Status::PausedForInput));
+                };
+                m.write(dst, val);
+                if is_code(dst) { *ip = 2; return Ok(None); }
+                *ip = 2;
+            },
+            2 => {
+                if *budget < 5 {
+                    return Ok(None);
+                }
+                *budget -= 5;
+
+                // 2: OUT [26]
+                *ip = 2;
+                io.write(m.read(checked_address(2, *rb, Opcode::Output, m.read(3))?));
+
+                // 4: MUL [26], #3, [27]
+                *ip = 4;
+                let a = m.read(checked_address(4, *rb, Opcode::Mul, m.read(5))?);
+                let b = m.read(6);
+                let dst = checked_address(4, *rb, Opcode::Mul, m.read(7))?;
+                m.write(dst, checked_arithmetic(4, *rb, Opcode::Mul, a, b)?);
+                if is_code(dst) { *ip = 8; *budget += 3; return Ok(None); }
+
+                // 8: OUT [27]
+                *ip = 8;
+                io.write(m.read(checked_address(8, *rb, Opcode::Output, m.read(9))?));
+
+                // 10: ADD [26], #-1, [26]
+                *ip = 10;
+                let a = m.read(checked_address(10, *rb, Opcode::Add, m.read(11))?);
+                let b = m.read(12);
+                let dst = checked_address(10, *rb, Opcode::Add, m.read(13))?;
+                m.write(dst, checked_arithmetic(10, *rb, Opcode::Add, a, b)?);
+                if is_code(dst) { *ip = 14; *budget += 1; return Ok(None); }
+
+                // 14: JT [26], #2
+                *ip = 14;
+                if m.read(checked_address(14, *rb, Opcode::JumpIfTrue, m.read(15))?) != 0 {
+                    *ip = checked_address(14, *rb, Opcode::JumpIfTrue, m.read(16))?;
+                    continue;
+                }
+                *ip = 17;
+            },
+            17 => {
+                if *budget < 4 {
+                    return Ok(None);
+                }
+                *budget -= 4;
+
+                // 17: ADD #0, [2], [2]
+                *ip = 17;
+                let a = m.read(18);
+                let b = m.read(checked_address(17, *rb, Opcode::Add, m.read(19))?);
+                let dst = checked_address(17, *rb, Opcode::Add, m.read(20))?;
+                m.write(dst, checked_arithmetic(17, *rb, Opcode::Add, a, b)?);
+                if is_code(dst) { *ip = 21; *budget += 3; return Ok(None); }
+
+                // 21: ARB #27
+                *ip = 21;
+                *rb = checked_rel_base(21, *rb, m.read(22))?;
+
+                // 23: OUT rb+0
+                *ip = 23;
+                io.write(m.read(relative_address(23, *rb, Opcode::Output, m.read(24))?));
+
+                // 25: HLT
+                *ip = 25;
+                return Ok(Some(Status::Halted));
+            },
+            _ => return Ok(None),
+        }
+    }
+}
@@ -1,8 +1,8 @@
 use std::collections::HashMap;
 
-use crate::{Day, computer::{Computer, Status}};
+use crate::{Day, computer::{Computer, Step}};
 
-use anyhow::{Result, anyhow, bail};
+use anyhow::{Context, Result, anyhow, bail};
 
 pub(crate) struct Day11 {
 }
@@ -16,41 +16,33 @@ impl Day for Day11 {
         let mut x: i32 = 0;
         let mut y: i32 = 0;
         loop {
-            let curr_color = panels.get(&(x, y)).unwrap_or(&Color::Black);
-            let input = match curr_color {
-                Color::Black => vec![0].into_iter(),
-                Color::White => vec![1].into_iter(),
-            };
-            let output = computer.run(input)?;
-            match output.status {
-                Status::Halted => {
+            let color_out = match computer.step()? {
+                Step::Halted => {
                     break;
                 },
-                Status::PausedForInput => {
-                    if output.outputs.len() == 0 {
-                        continue;
-                    }
-
-                    if output.outputs.len() != 2 {
-                        bail!("Unexpected output '{:?}'", output.outputs);
-                    }
-
-                    let color: Color = output.outputs[0].try_into()?;
-                    let dir_out = output.outputs[1];
-                    if dir_out != 0 && dir_out != 1 {
-                        bail!("Invalid rotate direction `{dir_out}`");
-                    }
-                    let rotate_dir = if dir_out == 0 { 
-                        RotateDirection::Left
-                    } else {
-                        RotateDirection::Right
-                    };
-
-                    panels.insert((x, y), color);
-                    dir = rotated(&dir, &rotate_dir);
-                    move_robot(&mut x, &mut y, &dir);
+                Step::NeedInput => {
+                    let curr_color = panels.get(&(x, y)).unwrap_or(&Color::Black);
+                    computer.push_input(camera_input(curr_color));
+                    continue;
                 },
+                Step::Output(val) => val,
+            };
+
+            let color: Color = color_out.try_into()?;
+            let dir_out = computer.run_until_output()?
+                .context("Robot halted before turning")?;
+            if dir_out != 0 && dir_out != 1 {
+                bail!("Invalid rotate direction `{dir_out}`");
             }
+            let rotate_dir = if dir_out == 0 { 
+                RotateDirection::Left
+            } else {
+                RotateDirection::Right
+            };
+
+            panels.insert((x, y), color);
+            dir = rotated(&dir, &rotate_dir);
+            move_robot(&mut x, &mut y, &dir);
         }
 
         println!("{}", panels.len());
@@ -66,48 +58,40 @@ impl Day for Day11 {
         let mut x: i32 = 0;
         let mut y: i32 = 0;
         loop {
-            let curr_color = panels.get(&(x, y)).unwrap_or_else(|| {
-                if panels.len() == 0 {
-                    // first one
-                    &Color::White
-                } else {
-                    &Color::Black
-                }
-            });
-            let input = match curr_color {
-                Color::Black => vec![0].into_iter(),
-                Color::White => vec![1].into_iter(),
-            };
-            let output = computer.run(input)?;
-            match output.status {
-                Status::Halted => {
+            let color_out = match computer.step()? {
+                Step::Halted => {
                     break;
                 },
-                Status::PausedForInput => {
-                    if output.outputs.len() == 0 {
-                        continue;
-                    }
-
-                    if output.outputs.len() != 2 {
-                        bail!("Unexpected output '{:?}'", output.outputs);
-                    }
-
-                    let color: Color = output.outputs[0].try_into()?;
-                    let dir_out = output.outputs[1];
-                    if dir_out != 0 && dir_out != 1 {
-                        bail!("Invalid rotate direction `{dir_out}`");
-                    }
-                    let rotate_dir = if dir_out == 0 { 
-                        RotateDirection::Left
-                    } else {
-                        RotateDirection::Right
-                    };
-
-                    panels.insert((x, y), color);
-                    dir = rotated(&dir, &rotate_dir);
-                    move_robot(&mut x, &mut y, &dir);
+                Step::NeedInput => {
+                    let curr_color = panels.get(&(x, y)).unwrap_or_else(|| {
+                        if panels.len() == 0 {
+                            // first one
+                            &Color::White
+                        } else {
+                            &Color::Black
+                        }
+                    });
+                    computer.push_input(camera_input(curr_color));
+                    continue;
                 },
+                Step::Output(val) => val,
+            };
+
+            let color: Color = color_out.try_into()?;
+            let dir_out = computer.run_until_output()?
+                .context("Robot halted before turning")?;
+            if dir_out != 0 && dir_out != 1 {
+                bail!("Invalid rotate direction `{dir_out}`");
             }
+            let rotate_dir = if dir_out == 0 { 
+                RotateDirection::Left
+            } else {
+                RotateDirection::Right
+            };
+
+            panels.insert((x, y), color);
+            dir = rotated(&dir, &rotate_dir);
+            move_robot(&mut x, &mut y, &dir);
         }
 
         let min_r = panels.iter().min_by(|a, b| a.0.0.cmp(&b.0.0)).unwrap().0.0;
@@ -158,6 +142,14 @@ impl TryFrom<i64> for Color {
     }
 }
 
+#[allow(clippy::needless_return)]
+fn camera_input(color: &Color) -> i64 {
+    return match color {
+        Color::Black => 0,
+        Color::White => 1,
+    };
+}
+
 enum Direction {
     Up,
     Right,
@@ -1,12 +1,13 @@
 use std::collections::HashSet;
 
-use crate::{Day, computer::Computer};
+use crate::{Day, computer::{Computer, IntcodeIo, Status}};
 
-use anyhow::{Result, anyhow};
+use anyhow::{Result, anyhow, bail};
 
 pub(crate) struct Day13 {
 }
 
+#[allow(clippy::needless_return)]
 impl Day for Day13 {
     fn part1(&mut self, input_file: String) -> Result<()> {
         let mut computer = Computer::from_file(&input_file)?;
@@ -33,90 +34,101 @@ impl Day for Day13 {
         let mut computer = Computer::from_file(&input_file)?;
         computer.memory.write(0, 2);
 
-        let inputs = std::iter::empty();
-        let output = computer.run(inputs)?;
-        // println!("{:?}\n", output.outputs);
+        let mut arcade = Arcade::default();
+        let status = computer.run_io(&mut arcade)?;
+        if let Some(e) = arcade.error {
+            return Err(e);
+        }
+        if status != Status::Halted {
+            bail!("Game stopped unexpectedly: {status:?}");
+        }
+        if !arcade.blocks.is_empty() {
+            bail!("Game over with {} block(s) left", arcade.blocks.len());
+        }
 
-        let mut score = 0;
-        let mut block_positions = HashSet::new();
-        let mut paddle = (0, 0);
-        let mut ball = (0, 0);
+        println!("{}", arcade.score);
 
-        for chunk in output.outputs.chunks(3) {
-            // println!("{chunk:?}");
-            let x = chunk[0];
-            let y = chunk[1];
-            if x == -1 && y == 0 {
-                score = chunk[2];
-            }
-            else {
-                let tile_id: TileId = (chunk[2] as u8).try_into()?;
-                match tile_id {
-                    TileId::Block => {
-                        block_positions.insert((x, y));
-                    },
-                    TileId::HorizontalPaddle => {
-                        paddle = (x, y);
-                    },
-                    TileId::Ball => {
-                        ball = (x, y);
-                    },
-                    TileId::Empty | TileId::Wall => {},
-                };
-            }
+        return Ok(());
+    }
+}
 
-        }
+// Plays the game while it runs, moving the paddle towards the ball every
+// time the joystick is read.
+#[derive(Default)]
+struct Arcade {
+    score: i64,
+    blocks: HashSet<(i64, i64)>,
+    paddle: (i64, i64),
+    ball: (i64, i64),
+    // Outputs of the tile currently being drawn.
+    tile: Vec<i64>,
+    error: Option<anyhow::Error>,
+}
 
-        while !block_positions.is_empty() {
-            let joystik = if paddle.0 < ball.0 {
-                Joystick::Right
-            } else if paddle.0 > ball.0 {
-                Joystick::Left
-            } else {
-                Joystick::Neutral
-            };
-            let joystick_val: i8 = joystik.into();
-
-            let inputs = vec![joystick_val as i64].into_iter();
-            let output = computer.run(inputs)?;
-            // println!("{:?}\n", output.outputs);
-
-            for chunk in output.outputs.chunks(3) {
-                // println!("{chunk:?}");
-                let x = chunk[0];
-                let y = chunk[1];
-                if x == -1 && y == 0 {
-                    score = chunk[2];
-                }
-                else {
-                    let tile_id: TileId = (chunk[2] as u8).try_into()?;
-
-                    // if block has dissapeared.
-                    if tile_id != TileId::Block {
-                        if block_positions.contains(&(x, y)) {
-                            block_positions.remove(&(x, y));
-                        }
-                    }
-
-                    match tile_id {
-                        TileId::HorizontalPaddle => {
-                            paddle = (x, y);
-                        },
-                        TileId::Ball => {
-                            ball = (x, y);
-                        },
-                        TileId::Empty | TileId::Wall | TileId::Block => {},
-                    };
-                }
-            }
+impl Arcade {
+    fn draw(&mut self, x: i64, y: i64, val: i64) -> Result<()> {
+        if x == -1 && y == 0 {
+            self.score = val;
+            return Ok(());
         }
 
-        println!("{score}");
+        let tile_id: TileId = (val as u8).try_into()?;
+        match tile_id {
+            TileId::Block => {
+                self.blocks.insert((x, y));
+            },
+            TileId::HorizontalPaddle => {
+                self.paddle = (x, y);
+            },
+            TileId::Ball => {
+                self.ball = (x, y);
+            },
+            TileId::Empty | TileId::Wall => {},
+        };
+
+        // if block has dissapeared.
+        if tile_id != TileId::Block {
+            self.blocks.remove(&(x, y));
+        }
 
         return Ok(());
     }
 }
 
+#[allow(clippy::needless_return)]
+impl IntcodeIo for Arcade {
+    fn read(&mut self) -> Option<i64> {
+        // Pausing the game ends the run, so the error gets reported.
+        if self.error.is_some() {
+            return None;
+        }
+
+        let joystik = if self.paddle.0 < self.ball.0 {
+            Joystick::Right
+        } else if self.paddle.0 > self.ball.0 {
+            Joystick::Left
+        } else {
+            Joystick::Neutral
+        };
+        let joystick_val: i8 = joystik.into();
+
+        return Some(joystick_val as i64);
+    }
+
+    fn write(&mut self, val: i64) {
+        self.tile.push(val);
+        if self.tile.len() < 3 {
+            return;
+        }
+
+        let (x, y, val) = (self.tile[0], self.tile[1], self.tile[2]);
+        self.tile.clear();
+        if let Err(e) = self.draw(x, y, val) && self.error.is_none() {
+            self.error = Some(e);
+        }
+    }
+}
+
 #[repr(u8)]
 #[derive(Debug, Eq, PartialEq)]
 enum TileId {
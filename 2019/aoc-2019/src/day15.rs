@@ -1,6 +1,6 @@
 use std::collections::{HashMap, HashSet, VecDeque};
 
-use crate::{Day, computer::{self, Computer}};
+use crate::{Day, computer::Computer};
 
 use anyhow::{Context, Result, anyhow, bail};
 
@@ -80,15 +80,12 @@ fn traverse_map(computer: &mut Computer, field_map: &mut HashMap<(i32, i32), boo
 
         let movement_input: u8 = (&movement).into();
         // println!("Input movement {movement:?}");
-        let input = vec![movement_input as i64].into_iter();
+        computer.push_input(movement_input as i64);
 
-        let output = computer.run(input)?;
+        let reply = computer.run_until_output()?
+            .context("Computer program terminated unexpectedly.")?;
 
-        if output.status == computer::Status::Halted {
-            bail!("Computer program terminated unexpectedly.");
-        }
-
-        let movement_status = (output.outputs[0] as u8).try_into()?;
+        let movement_status = (reply as u8).try_into()?;
         // println!("got output {movement_status:?}");
         match movement_status {
             Status::Moved => {
@@ -1,8 +1,8 @@
 use std::fmt::Display;
 
-use anyhow::{Result, bail};
+use anyhow::{Context, Result, bail};
 
-use crate::{Day, computer::{Computer, Status}};
+use crate::{Day, computer::{Computer, ascii::AsciiComputer}};
 
 pub(crate) struct Day17 {
 }
@@ -111,14 +111,22 @@ n
 "#;
         println!("{input_str}");
 
-        let input = input_str
-            .chars()
-            .map(|ch| ch as i64);
-
         let mut computer = Computer::from_file(&input_file)?;
         computer.memory.write(0, 2);
-        let output = computer.run(input)?;
-        println!("{:?}", output.outputs.iter().last().unwrap());
+
+        let mut ascii = AsciiComputer::new(computer);
+        for line in input_str.lines() {
+            ascii.read_until_prompt()?;
+            ascii.send_line(line)?;
+        }
+        ascii.read_until_prompt()?;
+
+        if !ascii.halted() {
+            bail!("Robot is still waiting for input");
+        }
+        let dust = ascii.answer()
+            .context("Robot didn't report the collected dust")?;
+        println!("{dust}");
 
         return Ok(());
     }
@@ -143,76 +151,64 @@ fn neighbour(pos: (usize, usize), dir: &Direction, dim: (usize, usize)) -> Optio
 }
 
 fn parse_input(input_file: &String) -> Result<View> {
-    let mut computer = Computer::from_file(input_file)?;
-    let input = std::iter::empty();
+    let computer = Computer::from_file(input_file)?;
+    let mut ascii = AsciiComputer::new(computer);
 
-    let output = computer.run(input)?;
+    let lines = ascii.read_until_prompt()?;
 
-    if output.status != Status::Halted {
+    if !ascii.halted() {
         bail!("Computer did not shut down properly");
     }
 
-    for o in &output.outputs {
-        if *o == 10 {
-            println!();
-        } else {
-            print!("{}", *o as u8 as char);
-        }
+    for line in &lines {
+        println!("{line}");
     }
 
     let mut view = Vec::new();
-    let mut row = Vec::new();
 
     let mut robot_r = 0;
     let mut robot_c = 0;
     let mut robot_dir = Direction::Up;
 
-    let mut r = 0;
-    let mut c = 0;
-
-    for o in output.outputs {
-        if o as u8 as char == '\n' {
-            if row.len() > 0 {
-                view.push(row);
-            }
-            row = Vec::new();
-            r += 1;
-            c = 0;
+    for (r, line) in lines.iter().enumerate() {
+        if line.is_empty() {
             continue;
         }
 
-        let tile = match o as u8 as char {
-            '#' => Tile::Scaffold,
-            '.' => Tile::Open,
-            '^' => {
-                robot_r = r;
-                robot_c = c;
-                robot_dir = Direction::Up;
-                Tile::Open
-            },
-            'v' => {
-                robot_r = r;
-                robot_c = c;
-                robot_dir = Direction::Down;
-                Tile::Open
-            },
-            '<' => {
-                robot_r = r;
-                robot_c = c;
-                robot_dir = Direction::Left;
-                Tile::Open
-            },
-            '>' => {
-                robot_r = r;
-                robot_c = c;
-                robot_dir = Direction::Right;
-                Tile::Open
-            },
-            c => bail!(format!("Invalid character '{c}' found.")),
-        };
-        row.push(tile);
-
-        c += 1;
+        let mut row = Vec::new();
+        for (c, ch) in line.chars().enumerate() {
+            let tile = match ch {
+                '#' => Tile::Scaffold,
+                '.' => Tile::Open,
+                '^' => {
+                    robot_r = r;
+                    robot_c = c;
+                    robot_dir = Direction::Up;
+                    Tile::Open
+                },
+                'v' => {
+                    robot_r = r;
+                    robot_c = c;
+                    robot_dir = Direction::Down;
+                    Tile::Open
+                },
+                '<' => {
+                    robot_r = r;
+                    robot_c = c;
+                    robot_dir = Direction::Left;
+                    Tile::Open
+                },
+                '>' => {
+                    robot_r = r;
+                    robot_c = c;
+                    robot_dir = Direction::Right;
+                    Tile::Open
+                },
+                c => bail!(format!("Invalid character '{c}' found.")),
+            };
+            row.push(tile);
+        }
+        view.push(row);
     }
 
     return Ok(View { view, robot_r, robot_c, robot_dir });
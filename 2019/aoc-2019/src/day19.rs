@@ -2,7 +2,7 @@ use std::{collections::HashMap, fs};
 
 use anyhow::{Context, Result};
 
-use crate::{Day, computer::Computer};
+use crate::{Day, computer::Computer, transpiled};
 
 pub(crate) struct Day19 {
 }
@@ -98,7 +98,10 @@ fn check(
     }
 
     let input = [pos.0 as i64, pos.1 as i64].into_iter();
-    let out = computer.run(input)?;
+    let out = match transpiled::DAY19 {
+        Some(program) => program.run(&mut computer, input)?,
+        None => computer.run(input)?,
+    };
 
     if out.outputs[0] == 0 {
         memo.insert(pos, false);
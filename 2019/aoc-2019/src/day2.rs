@@ -1,6 +1,11 @@
 use anyhow::{Result, bail};
 
-use crate::{Day, computer::Computer};
+use crate::{Day, computer::{Computer, Status, symbolic::{Condition, End}}, transpiled};
+
+// Way more than any noun and verb should need, bad ones may loop forever.
+const BUDGET: u64 = 1_000_000;
+
+const TARGET: i64 = 19690720;
 
 pub(crate) struct Day2;
 
@@ -19,7 +24,19 @@ impl Day for Day2 {
     }
 
     fn part2(&mut self, input_file: String) -> Result<()> {
-        let computer = Computer::from_file(&input_file)?;
+        let mut computer = Computer::from_file(&input_file)?;
+        if let Some((noun, verb)) = solve_symbolically(&computer)? {
+            println!("{}", 100 * noun + verb);
+            return Ok(());
+        }
+
+        // Programs the symbolic run can't handle are searched by brute force,
+        // compiled if there's a transpiled copy of the input. The budget alone
+        // catches loops there, livelock detection needs the interpreter.
+        let compiled = transpiled::DAY2;
+        if compiled.is_none() {
+            computer.enable_livelock_detection();
+        }
 
         for i in 0..=99 {
             for j in 0..=99 {
@@ -27,9 +44,16 @@ impl Day for Day2 {
                 c.memory.write(1, i);
                 c.memory.write(2, j);
                 let inputs = std::iter::empty();
-                let _ = c.run(inputs)?;
+                let output = match compiled {
+                    Some(program) => program.run_with_budget(&mut c, inputs, BUDGET),
+                    None => c.run_with_budget(inputs, BUDGET),
+                };
 
-                if c.memory.read(0) == 19690720 {
+                // A noun and verb that make the program fault, e.g. by
+                // overflowing, just aren't the answer.
+                if let Ok(output) = output
+                        && output.status == Status::Halted
+                        && c.memory.read(0) == TARGET {
                     let res = 100 * i + j;
                     println!("{res}");
                     return Ok(());
@@ -40,3 +64,46 @@ impl Day for Day2 {
         bail!("Result not found.");
     }
 }
+
+// Runs the program with symbolic noun and verb, which gives `mem[0]` as a
+// formula in both that can be solved for the target directly.
+#[allow(clippy::needless_return)]
+fn solve_symbolically(computer: &Computer) -> Result<Option<(i64, i64)>> {
+    let mut symbolic = computer.symbolic();
+    symbolic.symbolize(1, "noun");
+    symbolic.symbolize(2, "verb");
+
+    for path in symbolic.explore() {
+        if path.end != End::Halted {
+            continue;
+        }
+
+        let result = symbolic.read(&path, 0);
+        // Only the answer goes to stdout.
+        eprintln!("mem[0] = {}", symbolic.show(&result));
+
+        let mut conditions = path.conditions.clone();
+        conditions.push(Condition::equals(&result, TARGET));
+        let Some(solution) = symbolic.solve(&conditions, 0..=99) else {
+            continue;
+        };
+        let value = |name: &str| solution.iter()
+            .find(|(n, _)| n == name)
+            .map_or(0, |(_, val)| *val);
+        let (noun, verb) = (value("noun"), value("verb"));
+
+        // Symbolic arithmetic wraps where the VM would fail, so the answer
+        // is checked for real.
+        let mut c = computer.clone();
+        c.memory.write(1, noun);
+        c.memory.write(2, verb);
+        let output = c.run_with_budget(std::iter::empty(), BUDGET);
+        if let Ok(output) = output
+                && output.status == Status::Halted
+                && c.memory.read(0) == TARGET {
+            return Ok(Some((noun, verb)));
+        }
+    }
+
+    return Ok(None);
+}
@@ -1,6 +1,8 @@
-use crate::{Day, computer::{Computer, Status}};
+use std::sync::mpsc;
 
-use anyhow::{Context, Result, bail};
+use crate::{Day, computer::{Computer, ComputerError, Status}};
+
+use anyhow::{Context, Result, anyhow, bail};
 
 pub(crate) struct Day7 {
 }
@@ -20,55 +22,25 @@ impl Day for Day7 {
             let mut amp_e = computer.clone();
 
             let input_a = vec![p[0], 0].into_iter();
-            let output_a = amp_a.run(input_a)?;
-            match output_a.status {
-                Status::Halted => {},
-                Status::PausedForInput => {
-                    bail!("Amp A required more input values than expected.")
-                },
-            }
+            let output_a = run_amp(&mut amp_a, 'A', input_a)?;
 
             let input_b = vec![p[1]].into_iter()
-                .chain(output_a.outputs.into_iter());
-            let output_b = amp_b.run(input_b)?;
-            match output_b.status {
-                Status::Halted => {},
-                Status::PausedForInput => {
-                    bail!("Amp B required more input values than expected.")
-                },
-            }
+                .chain(output_a.into_iter());
+            let output_b = run_amp(&mut amp_b, 'B', input_b)?;
 
             let input_c = vec![p[2]].into_iter()
-                .chain(output_b.outputs.into_iter());
-            let output_c = amp_c.run(input_c)?;
-            match output_c.status {
-                Status::Halted => {},
-                Status::PausedForInput => {
-                    bail!("Amp C required more input values than expected.")
-                },
-            }
+                .chain(output_b.into_iter());
+            let output_c = run_amp(&mut amp_c, 'C', input_c)?;
 
             let input_d = vec![p[3]].into_iter()
-                .chain(output_c.outputs.into_iter());
-            let output_d = amp_d.run(input_d)?;
-            match output_d.status {
-                Status::Halted => {},
-                Status::PausedForInput => {
-                    bail!("Amp D required more input values than expected.")
-                },
-            }
+                .chain(output_c.into_iter());
+            let output_d = run_amp(&mut amp_d, 'D', input_d)?;
 
             let input_e = vec![p[4]].into_iter()
-                .chain(output_d.outputs.into_iter());
-            let output_e = amp_e.run(input_e)?;
-            match output_e.status {
-                Status::Halted => {},
-                Status::PausedForInput => {
-                    bail!("Amp E required more input values than expected.")
-                },
-            }
+                .chain(output_d.into_iter());
+            let output_e = run_amp(&mut amp_e, 'E', input_e)?;
 
-            let thrust_output = output_e.outputs.iter().next()
+            let thrust_output = output_e.first()
                 .context("Couldn't find thrust output.")?;
             if *thrust_output > res {
                 res = *thrust_output;
@@ -89,68 +61,53 @@ impl Day for Day7 {
         // let mut max_perm = Vec::new();
 
         for p in permutations {
-            let mut amps = vec![
-                computer.clone(),
-                computer.clone(),
-                computer.clone(),
-                computer.clone(),
-                computer.clone(),
-            ];
-            let mut halted = vec![false; 5];
-            let mut inputs = vec![
-                vec![],
-                vec![],
-                vec![],
-                vec![],
-                vec![],
-            ];
-
-            let mut curr = 0;
-            let mut first_run = true;
-            let mut sub_res = 0;
-
-            loop {
-                let amp = &mut amps[curr];
-                if halted[curr] {
-                    let amp_name = std::char::from_u32('A' as u32 + curr as u32)
-                        .unwrap();
-                    bail!("Amp {} is already halted, cannot run again", amp_name);
-                }
+            // Amp i reads from channel i and writes to channel i + 1, except
+            // for E whose outputs are passed on to A from here, so the last
+            // one can be kept.
+            let (senders, receivers): (Vec<_>, Vec<_>) = (0..5)
+                .map(|_| mpsc::channel::<i64>())
+                .unzip();
+            let (e_sender, e_receiver) = mpsc::channel();
+
+            for (idx, phase) in p.iter().enumerate() {
+                senders[idx].send(phase + 5)?;
+            }
+            senders[0].send(0)?;
 
-                let mut input_values = inputs[curr].clone();
-                let input_values = if first_run {
-                    if curr == 0 {
-                        let mut new = vec![p[0] + 5, 0];
-                        new.append(&mut input_values);
-                        new
-                    } else {
-                        let mut new = vec![p[curr] + 5];
-                        new.append(&mut input_values);
-                        new
-                    }
+            let mut threads = Vec::new();
+            for (idx, receiver) in receivers.into_iter().enumerate() {
+                let output = if idx == 4 {
+                    e_sender.clone()
                 } else {
-                    input_values
+                    senders[idx + 1].clone()
                 };
-
-                let res = amp.run(input_values.into_iter())?;
-                if res.status == Status::Halted {
-                    halted[curr] = true;
-                    if curr == 4 {
-                        if let Some(val) = res.outputs.last() {
-                            sub_res = *val;
-                        }
-                        break;
-                    }
-                }
-
-                if curr == 4 {
-                    first_run = false;
+                threads.push(computer.clone().spawn(receiver, output));
+            }
+            // Only A's input is fed from here, amps waiting on any other
+            // channel pause once the amp before them is done.
+            let a_sender = senders[0].clone();
+            drop(senders);
+            drop(e_sender);
+
+            let mut sub_res = None;
+            for val in e_receiver {
+                sub_res = Some(val);
+                // A has already halted after E's last output.
+                let _ = a_sender.send(val);
+            }
+            drop(a_sender);
+
+            for (idx, thread) in threads.into_iter().enumerate() {
+                let amp_name = std::char::from_u32('A' as u32 + idx as u32)
+                    .unwrap();
+                let (_, status) = thread.join()
+                    .map_err(|_| anyhow!("Amp {amp_name} panicked"))??;
+                if status != Status::Halted {
+                    bail!("Amp {amp_name} stopped without halting: {status:?}");
                 }
-                
-                curr = (curr + 1) % 5;
-                inputs[curr] = res.outputs;
             }
 
+            let sub_res = sub_res.context("Amp E produced no output.")?;
             if sub_res > res {
                 res = sub_res;
                 // max_perm = p;
@@ -164,6 +121,17 @@ impl Day for Day7 {
    }
 }
 
+#[allow(clippy::needless_return)]
+fn run_amp(amp: &mut Computer, name: char, inputs: impl Iterator<Item=i64>)
+        -> Result<Vec<i64>> {
+    return match amp.run_to_halt(inputs) {
+        Err(ComputerError::InputExhausted { .. }) => {
+            bail!("Amp {name} required more input values than expected.")
+        },
+        res => Ok(res?),
+    };
+}
+
 fn generate_permutations(n: i64) -> Vec<Vec<i64>> {
     let mut curr_state = Vec::new();
     return generate_permutations_inner(n, &mut curr_state);
@@ -21,6 +21,7 @@ mod day16;
 mod day17;
 mod day18;
 mod day19;
+mod transpiled;
 
 use crate::day1::Day1;
 use crate::day2::Day2;
@@ -112,8 +113,44 @@ fn get_day(n: i32) -> Result<Box<dyn Day>> {
     }
 }
 
+#[allow(clippy::needless_return)]
+fn run_tool(name: &str, args: &[String]) -> Option<Result<()>> {
+    return match name {
+        "asm" => Some(computer::asm::command(args)),
+        "bench" => Some(computer::bench::command(args)),
+        "cfg" => Some(computer::cfg::command(args)),
+        "coverage" => Some(computer::coverage::command(args)),
+        "debug" => Some(computer::debugger::command(args)),
+        "decompile" => Some(computer::decompile::command(args)),
+        "diff" => Some(computer::diff::command(args)),
+        "disasm" => Some(computer::disasm::command(args)),
+        "fuzz" => Some(computer::fuzz::command(args)),
+        "profile" => Some(computer::profile::command(args)),
+        "repl" => Some(computer::repl::command(args)),
+        "symbolic" => Some(computer::symbolic::command(args)),
+        "trace" => Some(computer::trace::command(args)),
+        "transpile" => Some(computer::transpile::command(args)),
+        _ => None,
+    };
+}
+
+#[allow(clippy::needless_return)]
 fn main() -> Result<()> {
-    let args: Vec<String> = env::args().collect();
+    let mut args: Vec<String> = env::args().collect();
+
+    // `--ext dbg,hltc` in front of everything else adds opcodes that aren't
+    // part of Intcode, for every day and tool.
+    if args.get(1).is_some_and(|arg| arg == "--ext") {
+        let names = args.get(2).context("Missing value for `--ext`")?;
+        for name in names.split(',') {
+            computer::opcodes::register_extension(name)?;
+        }
+        args.drain(1..3);
+    }
+
+    if args.len() >= 2 && let Some(res) = run_tool(&args[1], &args[2..]) {
+        return res;
+    }
 
     if args.len() < 4 {
         bail!("Not enough arguments");
@@ -123,6 +160,10 @@ fn main() -> Result<()> {
     let part = args[2].parse::<i32>().context("Part argument is not a number.")?;
     let input_file = args[3].clone();
 
+    return run_day(n, part, input_file);
+}
+
+fn run_day(n: i32, part: i32, input_file: String) -> Result<()> {
     let mut day = get_day(n)?;
 
     if part != 1 && part != 2 {
@@ -137,3 +178,25 @@ fn main() -> Result<()> {
         return Ok(());
     }
 }
+
+// Arguments shared by the tools that run a whole day: `<day> <input_file>
+// [part]`. Without a part both parts are run.
+#[allow(clippy::needless_return)]
+fn run_day_with_args(args: &[String]) -> Result<()> {
+    if args.len() < 2 {
+        bail!("Not enough arguments");
+    }
+
+    let n = args[0].parse::<i32>().context("Day argument is not a number.")?;
+    let input_file = args[1].clone();
+    let parts = match args.get(2) {
+        Some(part) => vec![part.parse::<i32>().context("Part argument is not a number.")?],
+        None => vec![1, 2],
+    };
+
+    for part in parts {
+        run_day(n, part, input_file.clone())?;
+    }
+
+    return Ok(());
+}
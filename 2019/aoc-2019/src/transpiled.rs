@@ -0,0 +1,10 @@
+use crate::computer::transpile::Transpiled;
+
+// Programs compiled with `aoc-2019 transpile`, for the days that run the same
+// program over and over. Puzzle inputs aren't part of the repo, so nothing is
+// compiled in by default: write e.g. `src/transpiled/day19.rs` from the input,
+// declare it with `mod day19;` here and set `DAY19` to `Some(&day19::PROGRAM)`.
+// A compiled program checks it's running the code it was generated from and
+// leaves anything else to the interpreter.
+pub(crate) static DAY2: Option<&Transpiled> = None;
+pub(crate) static DAY19: Option<&Transpiled> = None;